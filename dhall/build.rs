@@ -98,9 +98,15 @@ fn convert_abnf_to_pest() -> std::io::Result<()> {
     "##
     )?;
 
+    // Allow a line comment at the very end of the input without a trailing newline, as more
+    // recent versions of the standard do.
     writeln!(
         &mut file,
-        "final_expression = ${{ SOI ~ complete_expression ~ EOI }}"
+        r#"line_comment_prefix = _{{ "--" ~ not_end_of_line* }}"#
+    )?;
+    writeln!(
+        &mut file,
+        "final_expression = ${{ SOI ~ complete_expression ~ line_comment_prefix? ~ EOI }}"
     )?;
 
     writeln!(&mut file)?;
@@ -10,9 +10,23 @@ use crate::syntax::{
 };
 use crate::{Ctxt, Parsed};
 
-/// Built-ins
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub enum Builtin {
+/// Defines `Builtin` and `Builtin::ALL` from the same list, so that they can't get out of sync.
+macro_rules! builtins {
+    ($($name:ident,)*) => {
+        /// Built-ins
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum Builtin {
+            $($name,)*
+        }
+
+        impl Builtin {
+            /// All the builtins, in the order of their declaration.
+            pub const ALL: &'static [Builtin] = &[$(Builtin::$name,)*];
+        }
+    };
+}
+
+builtins! {
     Bool,
     Natural,
     Integer,
@@ -358,7 +372,11 @@ fn apply_builtin<'cx>(
             _ => Ret::DoneAsIs,
         },
         (Builtin::NaturalToInteger, [n]) => match &*n.kind() {
-            Num(Natural(n)) => Ret::NirKind(Num(Integer(*n as i64))),
+            // Naturals that don't fit in an `Integer` are left unevaluated rather than wrapped.
+            Num(Natural(n)) => match (*n).try_into() {
+                Ok(n) => Ret::NirKind(Num(Integer(n))),
+                Err(_) => Ret::DoneAsIs,
+            },
             _ => Ret::DoneAsIs,
         },
         (Builtin::NaturalShow, [n]) => match &*n.kind() {
@@ -392,7 +410,10 @@ fn apply_builtin<'cx>(
             _ => Ret::DoneAsIs,
         },
         (Builtin::IntegerNegate, [n]) => match &*n.kind() {
-            Num(Integer(n)) => Ret::NirKind(Num(Integer(-n))),
+            Num(Integer(n)) => match n.checked_neg() {
+                Some(n) => Ret::NirKind(Num(Integer(n))),
+                None => Ret::DoneAsIs,
+            },
             _ => Ret::DoneAsIs,
         },
         (Builtin::IntegerClamp, [n]) => match &*n.kind() {
@@ -1,10 +1,11 @@
 use elsa::vec::FrozenVec;
 use once_cell::sync::OnceCell;
+use std::cell::RefCell;
 use std::marker::PhantomData;
 use std::ops::{Deref, Index};
 
-use crate::semantics::{Import, ImportLocation, ImportNode};
-use crate::syntax::Span;
+use crate::semantics::{Hir, Import, ImportLocation, ImportNode};
+use crate::syntax::{Expr, Span};
 use crate::Typed;
 
 /////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -16,6 +17,7 @@ pub struct CtxtS<'cx> {
     imports: FrozenVec<Box<StoredImport<'cx>>>,
     import_alternatives: FrozenVec<Box<StoredImportAlternative<'cx>>>,
     import_results: FrozenVec<Box<StoredImportResult<'cx>>>,
+    normalization_trace: RefCell<Option<Vec<Expr>>>,
 }
 
 /// Context for the dhall compiler. Stores various global maps.
@@ -66,6 +68,8 @@ pub struct StoredImport<'cx> {
     cx: Ctxt<'cx>,
     pub base_location: ImportLocation,
     pub import: Import,
+    /// The `using` headers of a remote import, with their own imports resolved.
+    pub headers: Option<Hir<'cx>>,
     pub span: Span,
     result: OnceCell<ImportResultId<'cx>>,
 }
@@ -108,12 +112,14 @@ impl<'cx> Ctxt<'cx> {
         self,
         base_location: ImportLocation,
         import: Import,
+        headers: Option<Hir<'cx>>,
         span: Span,
     ) -> ImportId<'cx> {
         let stored = StoredImport {
             cx: self,
             base_location,
             import,
+            headers,
             span,
             result: OnceCell::new(),
         };
@@ -209,3 +215,30 @@ impl<'cx> Index<ImportResultId<'cx>> for CtxtS<'cx> {
         &self.import_results[id.0]
     }
 }
+
+/////////////////////////////////////////////////////////////////////////////////////////////////////
+// Normalization trace
+
+impl<'cx> Ctxt<'cx> {
+    /// Start recording the operations evaluated during normalization. Recording only costs
+    /// anything once it has been started.
+    pub fn start_normalization_trace(self) {
+        *self.0.normalization_trace.borrow_mut() = Some(Vec::new());
+    }
+    /// Stop recording, and return the operations evaluated since
+    /// `start_normalization_trace`, in the order they were evaluated. Each is the operation
+    /// as written in the source, before its arguments are evaluated.
+    pub fn take_normalization_trace(self) -> Vec<Expr> {
+        self.0
+            .normalization_trace
+            .borrow_mut()
+            .take()
+            .unwrap_or_default()
+    }
+    /// Record an evaluated operation, if a trace is being recorded.
+    pub(crate) fn trace_reduction(self, f: impl FnOnce() -> Expr) {
+        if let Some(trace) = &mut *self.0.normalization_trace.borrow_mut() {
+            trace.push(f());
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeSet;
+
+use crate::error::Error;
+use crate::semantics::{Nir, NirKind};
+use crate::syntax::Expr;
+use crate::{Ctxt, Parsed, ToExprOptions};
+
+/// A place where two expressions differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffEntry {
+    /// The path to the differing subexpression, e.g. `.server.hosts[0]`. Empty if the
+    /// expressions differ at the top level.
+    pub path: String,
+    /// The subexpression in the first expression, or `None` if it doesn't have that path.
+    pub left: Option<Expr>,
+    /// The subexpression in the second expression, or `None` if it doesn't have that path.
+    pub right: Option<Expr>,
+}
+
+/// Compares two expressions after normalizing them, and lists the places where they differ.
+///
+/// Records are compared field by field and lists element by element; any other subexpressions
+/// are compared as a whole, up to alpha-equivalence. The expressions must not contain imports.
+pub fn diff(a: &Expr, b: &Expr) -> Result<Vec<DiffEntry>, Error> {
+    Ctxt::with_new(|cx| {
+        let normalize = |e: &Expr| -> Result<_, Error> {
+            Ok(Parsed::from_expr_without_imports(e.clone())
+                .skip_resolve(cx)?
+                .typecheck(cx)?
+                .normalize(cx))
+        };
+        let a = normalize(a)?;
+        let b = normalize(b)?;
+
+        let mut entries = Vec::new();
+        diff_nir(cx, a.as_nir(), b.as_nir(), String::new(), &mut entries);
+        Ok(entries)
+    })
+}
+
+fn diff_nir<'cx>(
+    cx: Ctxt<'cx>,
+    a: &Nir<'cx>,
+    b: &Nir<'cx>,
+    path: String,
+    entries: &mut Vec<DiffEntry>,
+) {
+    let to_expr = |nir: &Nir<'cx>| nir.to_expr(cx, ToExprOptions::default());
+    match (a.kind(), b.kind()) {
+        (NirKind::RecordLit(kvs_a), NirKind::RecordLit(kvs_b)) => {
+            let labels: BTreeSet<_> =
+                kvs_a.keys().chain(kvs_b.keys()).collect();
+            for l in labels {
+                let path = format!("{}.{}", path, l);
+                match (kvs_a.get(l), kvs_b.get(l)) {
+                    (Some(a), Some(b)) => diff_nir(cx, a, b, path, entries),
+                    (a, b) => entries.push(DiffEntry {
+                        path,
+                        left: a.map(to_expr),
+                        right: b.map(to_expr),
+                    }),
+                }
+            }
+        }
+        (NirKind::NEListLit(xs_a), NirKind::NEListLit(xs_b)) => {
+            for i in 0..xs_a.len().max(xs_b.len()) {
+                let path = format!("{}[{}]", path, i);
+                match (xs_a.get(i), xs_b.get(i)) {
+                    (Some(a), Some(b)) => diff_nir(cx, a, b, path, entries),
+                    (a, b) => entries.push(DiffEntry {
+                        path,
+                        left: a.map(to_expr),
+                        right: b.map(to_expr),
+                    }),
+                }
+            }
+        }
+        _ if a == b => {}
+        _ => entries.push(DiffEntry {
+            path,
+            left: Some(to_expr(a)),
+            right: Some(to_expr(b)),
+        }),
+    }
+}
@@ -1,4 +1,5 @@
 use std::io::Error as IOError;
+use std::path::PathBuf;
 
 use crate::semantics::resolve::{CyclesStack, ImportLocation};
 use crate::syntax::{Import, ParseError};
@@ -34,12 +35,24 @@ pub enum ImportError {
     UnexpectedImport(Import<()>),
     ImportCycle(CyclesStack, ImportLocation),
     Url(url::ParseError),
+    /// Fetching a remote import failed, or remote imports are unavailable.
+    Remote(String),
+    /// The `using` headers of a remote import didn't evaluate to a list of text literals.
+    InvalidHeaders,
+    /// A local import points outside of the configured root directory.
+    ImportOutsideRoot {
+        path: PathBuf,
+    },
 }
 
 #[derive(Debug)]
 pub enum DecodeError {
     CBORError(serde_cbor::error::Error),
     WrongFormatError(String),
+    /// The data was encoded with a version tag, as done by older versions of the standard.
+    UnsupportedBinaryVersion {
+        found: String,
+    },
 }
 
 #[derive(Debug)]
@@ -57,6 +70,8 @@ pub struct TypeError {
 #[derive(Debug)]
 pub enum TypeMessage {
     Custom(String),
+    /// Several independent errors, collected when typechecking with `collect_errors`.
+    Multiple(Vec<TypeError>),
 }
 
 #[derive(Debug)]
@@ -73,21 +88,55 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         &self.kind
     }
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
 }
 
 impl TypeError {
     pub fn new(message: TypeMessage) -> Self {
         TypeError { message }
     }
+    /// Groups several errors into one. Nested groups are flattened, and a single error is
+    /// returned as is.
+    pub fn multiple(errors: Vec<TypeError>) -> Self {
+        let mut errors: Vec<_> = errors
+            .into_iter()
+            .flat_map(TypeError::into_errors)
+            .collect();
+        if errors.len() == 1 {
+            errors.pop().unwrap()
+        } else {
+            TypeError::new(TypeMessage::Multiple(errors))
+        }
+    }
+    pub fn message(&self) -> &TypeMessage {
+        &self.message
+    }
+    /// Splits a group of errors into its components.
+    pub fn into_errors(self) -> Vec<TypeError> {
+        match self.message {
+            TypeMessage::Multiple(errors) => errors,
+            message => vec![TypeError { message }],
+        }
+    }
 }
 
 impl std::fmt::Display for TypeError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         use TypeMessage::*;
-        let msg = match &self.message {
-            Custom(s) => format!("Type error: {}", s),
-        };
-        write!(f, "{}", msg)
+        match &self.message {
+            Custom(s) => write!(f, "Type error: {}", s),
+            Multiple(errs) => {
+                for (i, err) in errs.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", err)?;
+                }
+                Ok(())
+            }
+        }
     }
 }
 
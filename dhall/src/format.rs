@@ -0,0 +1,604 @@
+use itertools::Itertools;
+use std::ops::Range;
+
+use crate::error::Error;
+use crate::operations::OpKind;
+use crate::syntax::{
+    error_at, parse_expr_with_comments, Expr, ExprKind, PhasedExpr, PrintPhase,
+    QuotedLabel, Span,
+};
+
+/// The width that the formatter tries to fit lines within.
+const WIDTH: usize = 80;
+
+/// Formats Dhall source text canonically, without evaluating it.
+///
+/// Expressions that fit within 80 columns are printed on a single line, and longer ones are split
+/// over several lines. `let` bindings always get a line each. Comments are kept when they are at
+/// the start or the end of the input, or before a `let` binding or its `in`; since other comments
+/// can't be placed reliably, input containing them is refused with an error. Since the input is
+/// formatted from its syntax tree, some syntactic sugar like dotted record fields is expanded.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), dhall::error::Error> {
+/// let formatted = dhall::format("-- The port\nlet port=8080 in {port=port}")?;
+/// assert_eq!(formatted, "-- The port\nlet port = 8080\n\nin  { port = port }\n");
+/// # Ok(())
+/// # }
+/// ```
+pub fn format(source: &str) -> Result<String, Error> {
+    let (expr, comments) = parse_expr_with_comments(source)?;
+    let mut fmt = Formatter {
+        source,
+        comments: comments.into_iter().map(|c| (c, false)).collect(),
+    };
+    let range = span_range(&expr).unwrap_or(0..0);
+    let mut docs = Vec::new();
+    for comment in fmt.comments_in(0..range.start) {
+        docs.push(comment);
+        docs.push(hardline());
+    }
+    docs.push(fmt.expr(PhasedExpr(&expr, PrintPhase::Base)));
+    for comment in fmt.comments_in(range.end..source.len()) {
+        docs.push(hardline());
+        docs.push(comment);
+    }
+    if let Some((range, _)) = fmt.comments.iter().find(|(_, used)| !used) {
+        return Err(error_at(
+            source,
+            range.start,
+            "can't keep this comment; only comments at the start or end of the input, or \
+             before `let` or `in`, are kept"
+                .to_owned(),
+        )
+        .into());
+    }
+    let mut out = render(&concat(docs));
+    out.push('\n');
+    Ok(out)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    /// The comments of the source, in order, and whether they have been printed.
+    comments: Vec<(Range<usize>, bool)>,
+}
+
+impl Formatter<'_> {
+    /// Takes the comments within `range`.
+    fn comments_in(&mut self, range: Range<usize>) -> Vec<Doc> {
+        let first = self
+            .comments
+            .binary_search_by(|(c, _)| c.start.cmp(&range.start))
+            .unwrap_or_else(|i| i);
+        let mut docs = Vec::new();
+        for (c, used) in &mut self.comments[first..] {
+            if c.end > range.end {
+                break;
+            }
+            if !*used {
+                *used = true;
+                docs.push(text(self.source[c.clone()].trim_end()));
+            }
+        }
+        docs
+    }
+
+    /// Takes the comments just before `pos`, with only whitespace after them.
+    fn comments_before(&mut self, pos: usize) -> Vec<Doc> {
+        let end = self
+            .comments
+            .binary_search_by(|(c, _)| c.end.cmp(&pos))
+            .map_or_else(|i| i, |i| i + 1);
+        let mut start = pos;
+        let mut first = end;
+        while first > 0 {
+            let c = &self.comments[first - 1].0;
+            if !self.source[c.end..start].trim().is_empty() {
+                break;
+            }
+            start = c.start;
+            first -= 1;
+        }
+        self.comments_in(start..pos)
+    }
+
+    fn expr(&mut self, e: PhasedExpr) -> Doc {
+        let PhasedExpr(e, phase) = e;
+        let doc = self.unparenthesized(e);
+        if e.as_ref().needs_paren(phase) {
+            concat(vec![text("("), doc, text(")")])
+        } else {
+            doc
+        }
+    }
+
+    fn unparenthesized(&mut self, e: &Expr) -> Doc {
+        use ExprKind::*;
+        use OpKind::*;
+        let is_arrow = |l: &crate::syntax::Label| &String::from(l) == "_";
+        match e.as_ref().annotate_with_phases() {
+            Let(..) => self.let_chain(e),
+            Lam(..) => self.function(e),
+            Pi(l, ..) if !is_arrow(&l) => self.function(e),
+            Pi(..) => {
+                // Arrows are right-associative.
+                let mut operands = Vec::new();
+                let mut e = e;
+                loop {
+                    match e.as_ref().annotate_with_phases() {
+                        Pi(l, a, b) if is_arrow(&l) => {
+                            operands.push(self.expr(a));
+                            e = b.0;
+                        }
+                        _ => {
+                            operands.push(
+                                self.expr(PhasedExpr(e, PrintPhase::Base)),
+                            );
+                            break;
+                        }
+                    }
+                }
+                operator_chain("→", operands)
+            }
+            Op(BinOp(op, a, b)) => {
+                // Other operators are left-associative.
+                let mut rights = vec![b];
+                let mut left = a;
+                loop {
+                    match left.0.as_ref().annotate_with_phases() {
+                        Op(BinOp(op2, a, b))
+                            if op2 == op
+                                && !left.0.as_ref().needs_paren(left.1) =>
+                        {
+                            rights.push(b);
+                            left = a;
+                        }
+                        _ => break,
+                    }
+                }
+                let mut operands = vec![self.expr(left)];
+                for x in rights.into_iter().rev() {
+                    operands.push(self.expr(x));
+                }
+                operator_chain(&op.to_string(), operands)
+            }
+            Op(App(f, a)) => {
+                let mut args = vec![a];
+                let mut f = f;
+                while let Op(App(g, b)) = f.0.as_ref().annotate_with_phases() {
+                    args.push(b);
+                    f = g;
+                }
+                let mut docs = vec![self.expr(f)];
+                for x in args.into_iter().rev() {
+                    docs.push(self.expr(x));
+                }
+                application(docs, None)
+            }
+            SomeLit(a) => application(vec![text("Some"), self.expr(a)], None),
+            Op(Merge(a, b, t)) => {
+                let docs = vec![text("merge"), self.expr(a), self.expr(b)];
+                let t = t.map(|t| self.expr(t));
+                application(docs, t)
+            }
+            Op(ToMap(a, t)) => {
+                let docs = vec![text("toMap"), self.expr(a)];
+                let t = t.map(|t| self.expr(t));
+                application(docs, t)
+            }
+            Op(BoolIf(cond, left, right)) => align(group(concat(vec![
+                text("if "),
+                self.expr(cond),
+                line(),
+                text("then "),
+                self.expr(left),
+                line(),
+                text("else "),
+                self.expr(right),
+            ]))),
+            Annot(a, t) => align(group(concat(vec![
+                self.expr(a),
+                line(),
+                text(": "),
+                self.expr(t),
+            ]))),
+            Assert(t) => concat(vec![text("assert : "), self.expr(t)]),
+            Op(With(a, ls, b)) => {
+                let path = ls.iter().map(QuotedLabel).join(".");
+                let a = self.expr(a);
+                let b = self.expr(b);
+                align(group(concat(vec![
+                    a,
+                    nest(
+                        2,
+                        concat(vec![
+                            line(),
+                            text(format!("with {} = ", path)),
+                            b,
+                        ]),
+                    ),
+                ])))
+            }
+            Op(Field(a, l)) => concat(vec![
+                self.expr(a),
+                text(format!(".{}", QuotedLabel(&l))),
+            ]),
+            Op(Projection(a, ls)) => {
+                let ls = if ls.is_empty() {
+                    "{}".to_owned()
+                } else {
+                    format!("{{ {} }}", ls.iter().map(QuotedLabel).join(", "))
+                };
+                concat(vec![self.expr(a), text(format!(".{}", ls))])
+            }
+            Op(ProjectionByExpr(a, t)) => {
+                concat(vec![self.expr(a), text(".("), self.expr(t), text(")")])
+            }
+            Op(Completion(a, b)) => {
+                concat(vec![self.expr(a), text("::"), self.expr(b)])
+            }
+            EmptyListLit(t) => concat(vec![text("[] : "), self.expr(t)]),
+            NEListLit(es) => {
+                let items = es.into_iter().map(|x| self.expr(x)).collect();
+                enclosed("[", ",", "]", items)
+            }
+            RecordLit(kvs) if !kvs.is_empty() => {
+                let items = kvs
+                    .into_iter()
+                    .map(|(k, v)| field(&k, " =", Some(self.expr(v))))
+                    .collect();
+                enclosed("{", ",", "}", items)
+            }
+            RecordType(kts) if !kts.is_empty() => {
+                let items = kts
+                    .into_iter()
+                    .map(|(k, t)| field(&k, " :", Some(self.expr(t))))
+                    .collect();
+                enclosed("{", ",", "}", items)
+            }
+            UnionType(kts) if !kts.is_empty() => {
+                let items = kts
+                    .into_iter()
+                    .map(|(k, t)| field(&k, " :", t.map(|t| self.expr(t))))
+                    .collect();
+                enclosed("<", "|", ">", items)
+            }
+            // The remaining expressions have no subexpressions that could be split over several
+            // lines.
+            kind => text(kind.to_string()),
+        }
+    }
+
+    /// A chain of `let` bindings, one per line, keeping the comments around them.
+    fn let_chain(&mut self, mut e: &Expr) -> Doc {
+        let mut docs = Vec::new();
+        let mut prev_end = None;
+        while let ExprKind::Let(l, t, val, body) = e.kind() {
+            if let Some(range) = span_range(e) {
+                let comments = match prev_end {
+                    None => self.comments_before(range.start),
+                    Some(end) => self.comments_in(end..range.start),
+                };
+                for comment in comments {
+                    docs.push(comment);
+                    docs.push(hardline());
+                }
+            }
+            docs.push(text(format!("let {}", QuotedLabel(l))));
+            if let Some(t) = t {
+                docs.push(text(" : "));
+                docs.push(self.expr(PhasedExpr(t, PrintPhase::Base)));
+            }
+            docs.push(text(" ="));
+            let val_doc = self.expr(PhasedExpr(val, PrintPhase::Base));
+            docs.push(group(nest(6, concat(vec![line(), val_doc]))));
+            docs.push(hardline());
+            docs.push(hardline());
+            prev_end = span_range(val).map(|r| r.end);
+            e = body;
+        }
+        if let (Some(end), Some(range)) = (prev_end, span_range(e)) {
+            for comment in self.comments_in(end..range.start) {
+                docs.push(comment);
+                docs.push(hardline());
+            }
+        }
+        docs.push(text("in  "));
+        docs.push(self.expr(PhasedExpr(e, PrintPhase::Base)));
+        align(concat(docs))
+    }
+
+    /// A chain of lambdas and `forall`s, with their body indented on the next line if it doesn't
+    /// fit.
+    fn function(&mut self, mut e: &Expr) -> Doc {
+        let mut docs = Vec::new();
+        loop {
+            let (symbol, l, t, body) = match e.kind() {
+                ExprKind::Lam(l, t, body) => ("λ", l, t, body),
+                ExprKind::Pi(l, t, body) if &String::from(l) != "_" => {
+                    ("∀", l, t, body)
+                }
+                _ => break,
+            };
+            if !docs.is_empty() {
+                docs.push(line());
+            }
+            let t = self.expr(PhasedExpr(t, PrintPhase::Base));
+            docs.push(group(concat(vec![
+                text(format!("{}({}", symbol, QuotedLabel(l))),
+                nest(2, concat(vec![line(), text(": "), t])),
+                softline(),
+                text(") →"),
+            ])));
+            e = body;
+        }
+        let body = self.expr(PhasedExpr(e, PrintPhase::Base));
+        docs.push(nest(2, concat(vec![line(), body])));
+        align(group(concat(docs)))
+    }
+}
+
+fn span_range(e: &Expr) -> Option<Range<usize>> {
+    match e.span() {
+        Span::Parsed(span) => Some(span.range()),
+        _ => None,
+    }
+}
+
+/// `f a b`, or the arguments on their own lines, followed by an optional type annotation.
+fn application(docs: Vec<Doc>, annot: Option<Doc>) -> Doc {
+    let mut docs = docs.into_iter();
+    let head = docs.next().unwrap();
+    let mut args = Vec::new();
+    for x in docs {
+        args.push(line());
+        args.push(x);
+    }
+    let mut docs = vec![head, nest(2, concat(args))];
+    if let Some(t) = annot {
+        docs.push(line());
+        docs.push(text(": "));
+        docs.push(t);
+    }
+    align(group(concat(docs)))
+}
+
+/// `a + b + c`, or each operand on its own line, with the operators in front of them.
+fn operator_chain(op: &str, operands: Vec<Doc>) -> Doc {
+    let mut docs = vec![pad(op.chars().count() + 1)];
+    for (i, x) in operands.into_iter().enumerate() {
+        if i > 0 {
+            docs.push(line());
+            docs.push(text(format!("{} ", op)));
+        }
+        docs.push(x);
+    }
+    align(group(concat(docs)))
+}
+
+/// `{ a, b }`, or each item on its own line, with the separators in front of them.
+fn enclosed(open: &str, sep: &str, close: &str, items: Vec<Doc>) -> Doc {
+    let mut docs = Vec::new();
+    for (i, x) in items.into_iter().enumerate() {
+        if i == 0 {
+            docs.push(text(format!("{} ", open)));
+        } else {
+            docs.push(if sep == "," { softline() } else { line() });
+            docs.push(text(format!("{} ", sep)));
+        }
+        docs.push(x);
+    }
+    docs.push(line());
+    docs.push(text(close));
+    align(group(concat(docs)))
+}
+
+/// A record or union field, with its value indented on the next line if it doesn't fit.
+fn field(label: &crate::syntax::Label, sep: &str, value: Option<Doc>) -> Doc {
+    let label = text(QuotedLabel(label).to_string());
+    match value {
+        Some(value) => concat(vec![
+            label,
+            text(sep),
+            group(nest(4, concat(vec![line(), value]))),
+        ]),
+        None => label,
+    }
+}
+
+/// A document to lay out, in the style of Wadler's "A prettier printer". Each node knows how wide
+/// it is when printed on a single line, so that deciding whether a group fits doesn't need to
+/// look inside it again.
+struct Doc {
+    kind: DocKind,
+    /// The width of the document printed flat, or `None` if it contains a forced line break.
+    width: Option<usize>,
+}
+
+enum DocKind {
+    Text(String),
+    /// A space, or a line break if the enclosing group doesn't fit.
+    Line,
+    /// Nothing, or a line break if the enclosing group doesn't fit.
+    SoftLine,
+    /// A line break. The enclosing groups never fit.
+    HardLine,
+    /// Nothing, or this many spaces if the enclosing group doesn't fit.
+    Pad(usize),
+    /// Indents the line breaks of the document further.
+    Nest(usize, Box<Doc>),
+    /// Indents the line breaks of the document to the column where it starts.
+    Align(Box<Doc>),
+    /// A document that is printed flat if it fits on the line, and broken otherwise.
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+fn text(s: impl Into<String>) -> Doc {
+    let s = s.into();
+    let width = if s.contains('\n') {
+        None
+    } else {
+        Some(s.chars().count())
+    };
+    Doc {
+        kind: DocKind::Text(s),
+        width,
+    }
+}
+
+fn line() -> Doc {
+    Doc {
+        kind: DocKind::Line,
+        width: Some(1),
+    }
+}
+
+fn softline() -> Doc {
+    Doc {
+        kind: DocKind::SoftLine,
+        width: Some(0),
+    }
+}
+
+fn hardline() -> Doc {
+    Doc {
+        kind: DocKind::HardLine,
+        width: None,
+    }
+}
+
+fn pad(n: usize) -> Doc {
+    Doc {
+        kind: DocKind::Pad(n),
+        width: Some(0),
+    }
+}
+
+fn nest(n: usize, doc: Doc) -> Doc {
+    Doc {
+        width: doc.width,
+        kind: DocKind::Nest(n, Box::new(doc)),
+    }
+}
+
+fn align(doc: Doc) -> Doc {
+    Doc {
+        width: doc.width,
+        kind: DocKind::Align(Box::new(doc)),
+    }
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc {
+        width: doc.width,
+        kind: DocKind::Group(Box::new(doc)),
+    }
+}
+
+fn concat(docs: Vec<Doc>) -> Doc {
+    let width = docs.iter().map(|d| d.width).sum();
+    Doc {
+        kind: DocKind::Concat(docs),
+        width,
+    }
+}
+
+/// Lays out the document. The stack holds the documents left to print, with the indentation of
+/// their line breaks and whether they are printed flat.
+fn render(doc: &Doc) -> String {
+    use DocKind::*;
+    let mut out = Output::default();
+    let mut stack = vec![(0, false, doc)];
+    while let Some((indent, flat, doc)) = stack.pop() {
+        match &doc.kind {
+            Text(s) => out.write(s),
+            Line if flat => out.write(" "),
+            SoftLine if flat => {}
+            Pad(_) if flat => {}
+            Pad(n) => out.write(&" ".repeat(*n)),
+            Line | SoftLine | HardLine => out.newline(indent),
+            Nest(n, doc) => stack.push((indent + n, flat, doc)),
+            Align(doc) => stack.push((out.col, flat, doc)),
+            Group(doc) => {
+                let flat = flat || fits(doc, out.col, &stack);
+                stack.push((indent, flat, doc));
+            }
+            Concat(docs) => {
+                stack.extend(docs.iter().rev().map(|d| (indent, flat, d)))
+            }
+        }
+    }
+    out.text
+}
+
+#[derive(Default)]
+struct Output {
+    text: String,
+    col: usize,
+    /// Indentation is only written before text, so that empty lines have no trailing spaces.
+    at_line_start: bool,
+}
+
+impl Output {
+    fn write(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        if self.at_line_start {
+            self.text.push_str(&" ".repeat(self.col));
+            self.at_line_start = false;
+        }
+        self.text.push_str(s);
+        self.col = match s.rfind('\n') {
+            Some(i) => s[i + 1..].chars().count(),
+            None => self.col + s.chars().count(),
+        };
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.text.push('\n');
+        self.col = indent;
+        self.at_line_start = true;
+    }
+}
+
+/// Whether the group fits flat on the line starting at `col`, along with the text that follows it
+/// up to the next place where the line could break.
+fn fits(doc: &Doc, col: usize, rest: &[(usize, bool, &Doc)]) -> bool {
+    use DocKind::*;
+    let mut remaining = match doc.width {
+        Some(w) if col + w <= WIDTH => WIDTH - col - w,
+        _ => return false,
+    };
+    let mut rest = rest.iter().rev().map(|(_, flat, doc)| (*flat, *doc));
+    let mut pending = Vec::new();
+    loop {
+        let (flat, doc) = match pending.pop().or_else(|| rest.next()) {
+            Some(x) => x,
+            None => return true,
+        };
+        let width = match &doc.kind {
+            _ if flat => doc.width,
+            Text(_) if doc.width.is_none() => return true,
+            Text(_) => doc.width,
+            Pad(n) => Some(*n),
+            Line | SoftLine | HardLine => return true,
+            Nest(_, doc) | Align(doc) | Group(doc) => {
+                pending.push((false, &**doc));
+                continue;
+            }
+            Concat(docs) => {
+                pending.extend(docs.iter().rev().map(|d| (false, d)));
+                continue;
+            }
+        };
+        match width {
+            Some(w) if w <= remaining => remaining -= w,
+            _ => return false,
+        }
+    }
+}
@@ -0,0 +1,10 @@
+//! The types that describe imports, for tools that inspect or resolve them.
+//!
+//! An [`Import`] is what appears in the source, e.g. `../foo.dhall as Text`. Resolving it from
+//! the file it was found in gives an [`ImportLocation`], which is where its contents are
+//! actually read from.
+
+pub use crate::semantics::resolve::{ImportLocation, ImportLocationKind};
+pub use crate::syntax::{
+    FilePath, FilePrefix, Hash, Import, ImportMode, ImportTarget, Scheme, URL,
+};
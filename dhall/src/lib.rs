@@ -15,23 +15,37 @@
 
 pub mod builtins;
 pub mod ctxt;
+mod diff;
 pub mod error;
+mod format;
+pub mod imports;
 pub mod operations;
+mod resolve_only;
 pub mod semantics;
 pub mod syntax;
 pub mod utils;
 
-use std::path::Path;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use url::Url;
 
 use crate::error::{Error, TypeError};
+use crate::operations::{BinOp, OpKind};
 use crate::semantics::parse;
 use crate::semantics::resolve;
 use crate::semantics::resolve::ImportLocation;
-use crate::semantics::{typecheck, typecheck_with, Hir, Nir, Tir, Type};
-use crate::syntax::Expr;
+use crate::semantics::{
+    check_type, typecheck, typecheck_collecting_errors, typecheck_with, Hir,
+    HirKind, Nir, Tir, Type,
+};
+use crate::syntax::{Expr, ExprKind, ImportMode, Span};
 
 pub use ctxt::*;
+pub use diff::{diff, DiffEntry};
+pub use format::format;
+pub use resolve_only::resolve_only;
 
 #[derive(Debug, Clone)]
 pub struct Parsed(Expr, ImportLocation);
@@ -62,6 +76,48 @@ pub struct ToExprOptions {
     pub alpha: bool,
 }
 
+/// Controls import resolution
+#[derive(Debug, Clone, Default)]
+pub struct ResolveOptions<'a> {
+    /// Whether to resolve symlinks in the paths of local imports before caching them and checking
+    /// for cycles, so that a file reached through different paths is recognized as the same.
+    /// Relative imports are then resolved from the directory the file actually lives in.
+    pub canonicalize_paths: bool,
+    /// If set, local imports must point to files inside this directory, after resolving `..`
+    /// components and symlinks. Other local imports fail with `ImportOutsideRoot`.
+    pub root_directory: Option<PathBuf>,
+    /// Called with the location of each import before it is fetched, e.g. for audit logging.
+    pub on_import: Option<ImportHook<'a>>,
+    /// Values for `env:` imports. These take precedence over the environment of the process,
+    /// which is still used for the other variables.
+    pub env_vars: HashMap<String, String>,
+}
+
+/// A callback that observes imports as they are resolved. See `ResolveOptions::on_import`.
+#[derive(Clone)]
+pub struct ImportHook<'a>(Rc<ImportHookFn<'a>>);
+
+type ImportHookFn<'a> = dyn Fn(&ImportLocation, ImportMode) + 'a;
+
+impl<'a> ImportHook<'a> {
+    pub fn new(f: impl FnMut(&ImportLocation, ImportMode) + 'a) -> Self {
+        // `Fn` keeps the hook covariant in `'a`, which a `RefCell` in the `Rc` would prevent.
+        let f = RefCell::new(f);
+        ImportHook(Rc::new(move |location, mode| {
+            (f.borrow_mut())(location, mode)
+        }))
+    }
+    pub(crate) fn call(&self, location: &ImportLocation) {
+        (self.0)(location, location.mode())
+    }
+}
+
+impl std::fmt::Debug for ImportHook<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ImportHook")
+    }
+}
+
 impl Parsed {
     /// Construct from an `Expr`. This `Expr` will have imports disabled.
     pub fn from_expr_without_imports(e: Expr) -> Self {
@@ -88,6 +144,13 @@ impl Parsed {
     pub fn resolve<'cx>(self, cx: Ctxt<'cx>) -> Result<Resolved<'cx>, Error> {
         resolve::resolve(cx, self)
     }
+    pub fn resolve_with_options<'cx>(
+        self,
+        cx: Ctxt<'cx>,
+        opts: &ResolveOptions<'_>,
+    ) -> Result<Resolved<'cx>, Error> {
+        resolve::resolve_with_options(cx, self, opts)
+    }
     pub fn skip_resolve<'cx>(
         self,
         cx: Ctxt<'cx>,
@@ -104,6 +167,12 @@ impl Parsed {
         let Parsed(expr, import_location) = self;
         Parsed(expr.add_let_binding(label, value), import_location)
     }
+
+    /// Merges the parsed expression over `defaults`, as in `defaults // expr`.
+    pub fn prefer_over(self, defaults: Expr) -> Parsed {
+        let Parsed(expr, import_location) = self;
+        Parsed(expr.prefer_over(defaults), import_location)
+    }
 }
 
 impl<'cx> Resolved<'cx> {
@@ -117,6 +186,30 @@ impl<'cx> Resolved<'cx> {
     ) -> Result<Typed<'cx>, TypeError> {
         Ok(Typed::from_tir(typecheck_with(cx, &self.0, ty)?))
     }
+    /// Typechecks, optionally against a type, reporting all the independent errors found in
+    /// record and list literals instead of only the first one.
+    pub fn typecheck_collecting_errors(
+        &self,
+        cx: Ctxt<'cx>,
+        ty: Option<&Hir<'cx>>,
+    ) -> Result<Typed<'cx>, TypeError> {
+        Ok(Typed::from_tir(typecheck_collecting_errors(
+            cx, &self.0, ty,
+        )?))
+    }
+    /// Merges this expression over `base`, as in `base // self`. Unlike
+    /// [`Parsed::prefer_over()`], the two expressions may come from different files, since their
+    /// imports are already resolved.
+    pub fn prefer_over(self, base: Resolved<'cx>) -> Resolved<'cx> {
+        Resolved(Hir::new(
+            HirKind::Expr(ExprKind::Op(OpKind::BinOp(
+                BinOp::RightBiasedRecordMerge,
+                base.0,
+                self.0,
+            ))),
+            Span::Artificial,
+        ))
+    }
     /// Converts a value back to the corresponding AST expression.
     pub fn to_expr(&self, cx: Ctxt<'cx>) -> Expr {
         self.0.to_expr_noopts(cx)
@@ -130,6 +223,15 @@ impl<'cx> Typed<'cx> {
             ty: tir.ty().clone(),
         }
     }
+    /// Checks that the expression has the provided type, with the same error as
+    /// [`Resolved::typecheck_with()`] but without typechecking it again.
+    pub fn check_type(
+        &self,
+        cx: Ctxt<'cx>,
+        ty: &Hir<'cx>,
+    ) -> Result<(), TypeError> {
+        check_type(cx, &self.hir, &self.ty, ty)
+    }
     /// Reduce an expression to its normal form, performing beta reduction
     pub fn normalize(&self, cx: Ctxt<'cx>) -> Normalized<'cx> {
         Normalized(self.hir.eval_closed_expr(cx))
@@ -35,14 +35,19 @@ fn normalize_binop<'cx>(o: BinOp, x: Nir<'cx>, y: Nir<'cx>) -> Ret<'cx> {
 
         (NaturalPlus, Num(Natural(0)), _) => ret_nir(y),
         (NaturalPlus, _, Num(Natural(0))) => ret_nir(x),
-        (NaturalPlus, Num(Natural(x)), Num(Natural(y))) => {
+        // On overflow, leave the expression unevaluated instead of panicking.
+        (NaturalPlus, Num(Natural(x)), Num(Natural(y)))
+            if x.checked_add(*y).is_some() =>
+        {
             ret_kind(Num(Natural(x + y)))
         }
         (NaturalTimes, Num(Natural(0)), _) => ret_kind(Num(Natural(0))),
         (NaturalTimes, _, Num(Natural(0))) => ret_kind(Num(Natural(0))),
         (NaturalTimes, Num(Natural(1)), _) => ret_nir(y),
         (NaturalTimes, _, Num(Natural(1))) => ret_nir(x),
-        (NaturalTimes, Num(Natural(x)), Num(Natural(y))) => {
+        (NaturalTimes, Num(Natural(x)), Num(Natural(y)))
+            if x.checked_mul(*y).is_some() =>
+        {
             ret_kind(Num(Natural(x * y)))
         }
 
@@ -9,7 +9,7 @@ use crate::semantics::{
     merge_maps, mk_span_err, mkerr, Binder, Closure, Hir, HirKind, Nir,
     NirKind, Tir, TyEnv, Type,
 };
-use crate::syntax::{Const, ExprKind, Span};
+use crate::syntax::{Const, ExprKind, Label, Span};
 
 fn check_rectymerge(
     span: &Span,
@@ -178,8 +178,11 @@ fn typecheck_merge<'cx>(
         _ => return span_err("Merge2ArgMustBeUnionOrOptional"),
     };
 
-    let mut inferred_type = None;
-    for (x, handler_type) in handlers {
+    // Sort the handlers so that errors are reported deterministically.
+    let mut sorted_handlers: Vec<_> = handlers.iter().collect();
+    sorted_handlers.sort_by_key(|(x, _)| *x);
+    let mut inferred_type: Option<(&Label, Type)> = None;
+    for (x, handler_type) in sorted_handlers {
         let handler_return_type: Type = match variants.get(x) {
             // Union alternative with type
             Some(Some(variant_type)) => match handler_type.kind() {
@@ -252,22 +255,97 @@ fn typecheck_merge<'cx>(
             },
             // Union alternative without type
             Some(None) => Type::new_infer_universe(env, handler_type.clone())?,
-            None => return span_err("MergeHandlerMissingVariant"),
+            None => {
+                return mkerr(
+                    ErrorBuilder::new(format!(
+                        "handler `{}` has no matching alternative",
+                        x
+                    ))
+                    .span_err(span, format!("in this merge expression"))
+                    .span_err(
+                        record.span(),
+                        format!("this record has a handler for `{}`", x),
+                    )
+                    .span_help(
+                        scrut.span(),
+                        format!(
+                            "but the merged value has type: `{}`",
+                            scrut_type.to_expr_tyenv(env)
+                        ),
+                    )
+                    .format(),
+                )
+            }
         };
         match &inferred_type {
-            None => inferred_type = Some(handler_return_type),
-            Some(t) => {
+            None => inferred_type = Some((x, handler_return_type)),
+            Some((y, t)) => {
                 if t != &handler_return_type {
-                    return span_err("MergeHandlerTypeMismatch");
+                    return mkerr(
+                        ErrorBuilder::new(format!(
+                            "merge handlers return different types"
+                        ))
+                        .span_err(span, format!("in this merge expression"))
+                        .span_err(
+                            record.span(),
+                            format!(
+                                "the handler for `{}` returns: `{}`",
+                                y,
+                                t.to_expr_tyenv(env)
+                            ),
+                        )
+                        .span_err(
+                            record.span(),
+                            format!(
+                                "but the handler for `{}` returns: `{}`",
+                                x,
+                                handler_return_type.to_expr_tyenv(env)
+                            ),
+                        )
+                        .format(),
+                    );
                 }
             }
         }
     }
-    for x in variants.keys() {
-        if !handlers.contains_key(x) {
-            return span_err("MergeVariantMissingHandler");
-        }
+    let mut missing: Vec<_> = variants
+        .keys()
+        .filter(|x| !handlers.contains_key(*x))
+        .collect();
+    missing.sort();
+    if !missing.is_empty() {
+        // Report all the missing handlers at once.
+        let names = missing
+            .iter()
+            .map(|x| format!("`{}`", x))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let (title, label) = if missing.len() == 1 {
+            (
+                format!("handler for alternative {} is missing", names),
+                format!("this record has no handler for {}", names),
+            )
+        } else {
+            (
+                format!("handlers for alternatives {} are missing", names),
+                format!("this record has no handlers for {}", names),
+            )
+        };
+        return mkerr(
+            ErrorBuilder::new(title)
+                .span_err(span, format!("in this merge expression"))
+                .span_err(record.span(), label)
+                .span_help(
+                    scrut.span(),
+                    format!(
+                        "the merged value has type: `{}`",
+                        scrut_type.to_expr_tyenv(env)
+                    ),
+                )
+                .format(),
+        );
     }
+    let inferred_type = inferred_type.map(|(_, t)| t);
 
     let type_annot = type_annot
         .as_ref()
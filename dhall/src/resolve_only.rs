@@ -0,0 +1,28 @@
+use std::path::Path;
+
+use crate::error::Error;
+use crate::semantics::parse;
+use crate::syntax::Expr;
+use crate::Ctxt;
+
+/// Parses Dhall source text and resolves its imports, without typechecking or normalizing it.
+///
+/// Relative imports are resolved from `base_dir`. The result is a self-contained expression in
+/// which every import has been replaced by its contents; this is useful for tools that want to
+/// inspect or ship a configuration together with everything it imports. As the standard
+/// requires, each imported expression is typechecked and normalized before being inlined, but the
+/// top-level expression is returned as written.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), dhall::error::Error> {
+/// let expr = dhall::resolve_only("let x = 1 + 1 in x", std::path::Path::new("."))?;
+/// assert_eq!(expr.to_string(), "let x = 1 + 1 in x");
+/// # Ok(())
+/// # }
+/// ```
+pub fn resolve_only(source: &str, base_dir: &Path) -> Result<Expr, Error> {
+    let parsed = parse::parse_str_in_dir(source, base_dir)?;
+    Ctxt::with_new(|cx| Ok(parsed.resolve(cx)?.to_expr(cx)))
+}
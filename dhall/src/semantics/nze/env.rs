@@ -1,4 +1,5 @@
-use crate::semantics::{AlphaVar, Nir, NirKind};
+use crate::semantics::{AlphaVar, NameEnv, Nir, NirKind};
+use crate::syntax::Label;
 use crate::Ctxt;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,9 +13,9 @@ pub enum NzVar {
 #[derive(Debug, Clone)]
 enum EnvItem<'cx, T> {
     // Variable is bound with given type
-    Kept(T),
+    Kept(Label, T),
     // Variable has been replaced by corresponding value
-    Replaced(Nir<'cx>, T),
+    Replaced(Label, Nir<'cx>, T),
 }
 
 #[derive(Debug, Clone)]
@@ -63,34 +64,49 @@ impl<'cx, T: Clone> ValEnv<'cx, T> {
             .items
             .iter()
             .map(|i| match i {
-                EnvItem::Kept(_) => EnvItem::Kept(()),
-                EnvItem::Replaced(val, _) => EnvItem::Replaced(val.clone(), ()),
+                EnvItem::Kept(x, _) => EnvItem::Kept(x.clone(), ()),
+                EnvItem::Replaced(x, val, _) => {
+                    EnvItem::Replaced(x.clone(), val.clone(), ())
+                }
             })
             .collect();
         ValEnv { cx: self.cx, items }
     }
 
-    pub fn insert_type(&self, ty: T) -> Self {
+    /// The names of the variables in scope, to print expressions evaluated in this environment.
+    pub fn to_nameenv(&self) -> NameEnv {
+        let mut names = NameEnv::new();
+        for item in &self.items {
+            match item {
+                EnvItem::Kept(x, _) | EnvItem::Replaced(x, _, _) => {
+                    names.insert_mut(x)
+                }
+            }
+        }
+        names
+    }
+
+    pub fn insert_type(&self, x: &Label, ty: T) -> Self {
         let mut env = self.clone();
-        env.items.push(EnvItem::Kept(ty));
+        env.items.push(EnvItem::Kept(x.clone(), ty));
         env
     }
-    pub fn insert_value(&self, e: Nir<'cx>, ty: T) -> Self {
+    pub fn insert_value(&self, x: &Label, e: Nir<'cx>, ty: T) -> Self {
         let mut env = self.clone();
-        env.items.push(EnvItem::Replaced(e, ty));
+        env.items.push(EnvItem::Replaced(x.clone(), e, ty));
         env
     }
     pub fn lookup_val(&self, var: AlphaVar) -> NirKind<'cx> {
         let idx = self.items.len() - 1 - var.idx();
         match &self.items[idx] {
-            EnvItem::Kept(_) => NirKind::Var(NzVar::new(idx)),
-            EnvItem::Replaced(x, _) => x.kind().clone(),
+            EnvItem::Kept(..) => NirKind::Var(NzVar::new(idx)),
+            EnvItem::Replaced(_, x, _) => x.kind().clone(),
         }
     }
     pub fn lookup_ty(&self, var: AlphaVar) -> T {
         let idx = self.items.len() - 1 - var.idx();
         match &self.items[idx] {
-            EnvItem::Kept(ty) | EnvItem::Replaced(_, ty) => ty.clone(),
+            EnvItem::Kept(_, ty) | EnvItem::Replaced(_, _, ty) => ty.clone(),
         }
     }
 }
@@ -35,7 +35,11 @@ pub enum Thunk<'cx> {
 #[derive(Debug, Clone)]
 pub enum Closure<'cx> {
     /// Normal closure
-    Closure { env: NzEnv<'cx>, body: Hir<'cx> },
+    Closure {
+        binder: Label,
+        env: NzEnv<'cx>,
+        body: Hir<'cx>,
+    },
     /// Closure that ignores the argument passed
     ConstantClosure { body: Nir<'cx> },
 }
@@ -311,8 +315,9 @@ impl<'cx> Thunk<'cx> {
 }
 
 impl<'cx> Closure<'cx> {
-    pub fn new(env: &NzEnv<'cx>, body: Hir<'cx>) -> Self {
+    pub fn new(binder: &Label, env: &NzEnv<'cx>, body: Hir<'cx>) -> Self {
         Closure::Closure {
+            binder: binder.clone(),
             env: env.clone(),
             body,
         }
@@ -324,8 +329,8 @@ impl<'cx> Closure<'cx> {
 
     pub fn apply(&self, val: Nir<'cx>) -> Nir<'cx> {
         match self {
-            Closure::Closure { env, body, .. } => {
-                body.eval(env.insert_value(val, ()))
+            Closure::Closure { binder, env, body } => {
+                body.eval(env.insert_value(binder, val, ()))
             }
             Closure::ConstantClosure { body, .. } => body.clone(),
         }
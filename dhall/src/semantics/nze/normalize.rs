@@ -173,7 +173,7 @@ pub fn normalize_hir<'cx>(env: &NzEnv<'cx>, hir: &Hir<'cx>) -> NirKind<'cx> {
             NirKind::LamClosure {
                 binder: Binder::new(binder.clone()),
                 annot,
-                closure: Closure::new(env, body.clone()),
+                closure: Closure::new(binder, env, body.clone()),
             }
         }
         HirKind::Expr(ExprKind::Pi(binder, annot, body)) => {
@@ -181,17 +181,22 @@ pub fn normalize_hir<'cx>(env: &NzEnv<'cx>, hir: &Hir<'cx>) -> NirKind<'cx> {
             NirKind::PiClosure {
                 binder: Binder::new(binder.clone()),
                 annot,
-                closure: Closure::new(env, body.clone()),
+                closure: Closure::new(binder, env, body.clone()),
             }
         }
-        HirKind::Expr(ExprKind::Let(_, _, val, body)) => {
+        HirKind::Expr(ExprKind::Let(binder, _, val, body)) => {
+            // No substitution happens: the value is evaluated at most once, lazily, and shared
+            // between all the occurrences of the variable.
             let val = val.eval(env);
-            body.eval(env.insert_value(val, ())).kind().clone()
+            body.eval(env.insert_value(binder, val, ())).kind().clone()
         }
         HirKind::Expr(ExprKind::Builtin(b)) => {
             NirKind::from_builtin_env(*b, env.clone())
         }
         HirKind::Expr(e) => {
+            if let ExprKind::Op(_) = e {
+                env.cx().trace_reduction(|| hir.to_expr_nzenv(env));
+            }
             let e = e.map_ref(|hir| hir.eval(env));
             normalize_one_layer(e)
         }
@@ -15,12 +15,29 @@ pub fn parse_file(f: &Path) -> Result<Parsed, Error> {
 }
 
 pub fn parse_remote(url: Url) -> Result<Parsed, Error> {
-    let body = download_http_text(url.clone())?;
+    parse_remote_with_headers(url, &[])
+}
+
+/// Downloads and parses dhall code, sending the given HTTP headers with the request.
+pub fn parse_remote_with_headers(
+    url: Url,
+    headers: &[(String, String)],
+) -> Result<Parsed, Error> {
+    let body = download_http_text(url.clone(), headers)?;
     let expr = parse_expr(&body)?;
     let root = ImportLocation::remote_dhall_code(url);
     Ok(Parsed(expr, root))
 }
 
+/// Parses the contents of an environment variable as dhall code. Parse errors point at
+/// `env:NAME`.
+pub fn parse_env(var_name: &str, val: &str) -> Result<Parsed, Error> {
+    let expr = parse_expr(val)
+        .map_err(|e| e.with_path(&format!("env:{}", var_name)))?;
+    let root = ImportLocation::env_dhall_code(var_name.to_owned());
+    Ok(Parsed(expr, root))
+}
+
 pub fn parse_str(s: &str) -> Result<Parsed, Error> {
     let expr = parse_expr(s)?;
     let root = ImportLocation::dhall_code_of_unknown_origin();
@@ -39,3 +56,12 @@ pub fn parse_binary_file(f: &Path) -> Result<Parsed, Error> {
     let root = ImportLocation::local_dhall_code(f.to_owned());
     Ok(Parsed(expr, root))
 }
+
+/// Parses dhall code whose relative imports are relative to `dir`.
+pub fn parse_str_in_dir(s: &str, dir: &Path) -> Result<Parsed, Error> {
+    let expr = parse_expr(s)?;
+    // Relative imports are resolved from the parent of the importing file, so this name is
+    // never read.
+    let root = ImportLocation::local_dhall_code(dir.join("<input>"));
+    Ok(Parsed(expr, root))
+}
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use crate::error::{Error, ImportError};
 use crate::semantics::{check_hash, AlphaVar, Cache, ImportLocation, VarEnv};
 use crate::syntax::{Hash, Label, V};
-use crate::{Ctxt, ImportId, ImportResultId, Typed};
+use crate::{Ctxt, ImportId, ImportResultId, ResolveOptions, Typed};
 
 /// Environment for resolving names.
 #[derive(Debug, Clone, Default)]
@@ -14,11 +14,12 @@ pub struct NameEnv {
 pub type CyclesStack = Vec<ImportLocation>;
 
 /// Environment for resolving imports
-pub struct ImportEnv<'cx> {
+pub struct ImportEnv<'cx, 'o> {
     cx: Ctxt<'cx>,
     disk_cache: Option<Cache>, // `None` if it failed to initialize
     mem_cache: HashMap<ImportLocation, ImportResultId<'cx>>,
     stack: CyclesStack,
+    options: ResolveOptions<'o>,
 }
 
 impl NameEnv {
@@ -65,19 +66,26 @@ impl NameEnv {
     }
 }
 
-impl<'cx> ImportEnv<'cx> {
+impl<'cx, 'o> ImportEnv<'cx, 'o> {
     pub fn new(cx: Ctxt<'cx>) -> Self {
+        Self::with_options(cx, ResolveOptions::default())
+    }
+    pub fn with_options(cx: Ctxt<'cx>, options: ResolveOptions<'o>) -> Self {
         ImportEnv {
             cx,
             disk_cache: Cache::new().ok(),
             mem_cache: Default::default(),
             stack: Default::default(),
+            options,
         }
     }
 
     pub fn cx(&self) -> Ctxt<'cx> {
         self.cx
     }
+    pub fn options(&self) -> &ResolveOptions<'o> {
+        &self.options
+    }
 
     pub fn get_from_mem_cache(
         &self,
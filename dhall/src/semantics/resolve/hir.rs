@@ -73,6 +73,12 @@ impl<'cx> Hir<'cx> {
         let mut env = env.as_nameenv().clone();
         hir_to_expr(cx, self, opts, &mut env)
     }
+    /// Converts a Hir expr that is being evaluated in `env` back to the corresponding AST
+    /// expression, naming its free variables after their binders.
+    pub fn to_expr_nzenv(&self, env: &NzEnv<'cx>) -> Expr {
+        let opts = ToExprOptions { alpha: false };
+        hir_to_expr(env.cx(), self, opts, &mut env.to_nameenv())
+    }
 
     /// Typecheck the Hir.
     pub fn typecheck<'hir>(
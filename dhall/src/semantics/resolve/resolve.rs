@@ -1,8 +1,8 @@
 use itertools::Itertools;
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use url::Url;
 
 use crate::builtins::Builtin;
@@ -16,16 +16,15 @@ use crate::syntax::{
     UnspannedExpr, URL,
 };
 use crate::{
-    Ctxt, ImportAlternativeId, ImportId, ImportResultId, Parsed, Resolved,
-    Typed,
+    Ctxt, ImportAlternativeId, ImportId, ImportResultId, Parsed,
+    ResolveOptions, Resolved, Typed,
 };
 
-// TODO: evaluate import headers
 pub type Import = syntax::Import<()>;
 
 /// The location of some data, usually some dhall code.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-enum ImportLocationKind {
+pub enum ImportLocationKind {
     /// Local file
     Local(PathBuf),
     /// Remote file
@@ -34,7 +33,7 @@ enum ImportLocationKind {
     Env(String),
     /// Data without a location; chaining will start from current directory.
     Missing,
-    /// Token to signal that thi sfile should contain no imports.
+    /// Token to signal that this file should contain no imports.
     NoImport,
 }
 
@@ -43,6 +42,8 @@ enum ImportLocationKind {
 pub struct ImportLocation {
     kind: ImportLocationKind,
     mode: ImportMode,
+    /// The evaluated `using` headers of a remote import.
+    headers: Vec<(String, String)>,
 }
 
 impl ImportLocationKind {
@@ -100,8 +101,10 @@ impl ImportLocationKind {
                     FilePrefix::Parent => {
                         url = url.join("..")?;
                     }
-                    FilePrefix::Absolute => panic!("error"),
-                    FilePrefix::Home => panic!("error"),
+                    // A remote file may not refer to local files.
+                    FilePrefix::Absolute | FilePrefix::Home => {
+                        return Err(ImportError::SanityCheck.into())
+                    }
                 }
                 url = url.join(&path.file_path.join("/"))?;
                 ImportLocationKind::Remote(url)
@@ -110,18 +113,22 @@ impl ImportLocationKind {
         })
     }
 
-    fn fetch_dhall(&self) -> Result<Parsed, Error> {
+    fn fetch_dhall(
+        &self,
+        headers: &[(String, String)],
+        env_vars: &HashMap<String, String>,
+    ) -> Result<Parsed, Error> {
         Ok(match self {
             ImportLocationKind::Local(path) => Parsed::parse_file(path)?,
             ImportLocationKind::Remote(url) => {
-                Parsed::parse_remote(url.clone())?
+                crate::semantics::parse::parse_remote_with_headers(
+                    url.clone(),
+                    headers,
+                )?
             }
             ImportLocationKind::Env(var_name) => {
-                let val = match env::var(var_name) {
-                    Ok(val) => val,
-                    Err(_) => return Err(ImportError::MissingEnvVar.into()),
-                };
-                Parsed::parse_str(&val)?
+                let val = lookup_env_var(env_vars, var_name)?;
+                crate::semantics::parse::parse_env(var_name, &val)?
             }
             ImportLocationKind::Missing => {
                 return Err(ImportError::Missing.into())
@@ -130,17 +137,22 @@ impl ImportLocationKind {
         })
     }
 
-    fn fetch_text(&self) -> Result<String, Error> {
+    fn fetch_text(
+        &self,
+        headers: &[(String, String)],
+        env_vars: &HashMap<String, String>,
+    ) -> Result<String, Error> {
         Ok(match self {
             ImportLocationKind::Local(path) => {
                 let path = resolve_home(path)?;
                 std::fs::read_to_string(path)?
             }
-            ImportLocationKind::Remote(url) => download_http_text(url.clone())?,
-            ImportLocationKind::Env(var_name) => match env::var(var_name) {
-                Ok(val) => val,
-                Err(_) => return Err(ImportError::MissingEnvVar.into()),
-            },
+            ImportLocationKind::Remote(url) => {
+                download_http_text(url.clone(), headers)?
+            }
+            ImportLocationKind::Env(var_name) => {
+                lookup_env_var(env_vars, var_name)?
+            }
             ImportLocationKind::Missing => {
                 return Err(ImportError::Missing.into())
             }
@@ -177,28 +189,94 @@ impl ImportLocationKind {
 }
 
 impl ImportLocation {
+    /// Where the data is located.
+    pub fn kind(&self) -> &ImportLocationKind {
+        &self.kind
+    }
+    /// How the data is interpreted.
+    pub fn mode(&self) -> ImportMode {
+        self.mode
+    }
+    /// The HTTP headers sent when fetching a remote import, from its `using` clause.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
     pub fn dhall_code_of_unknown_origin() -> Self {
         ImportLocation {
             kind: ImportLocationKind::Missing,
             mode: ImportMode::Code,
+            headers: Vec::new(),
         }
     }
     pub fn dhall_code_without_imports() -> Self {
         ImportLocation {
             kind: ImportLocationKind::NoImport,
             mode: ImportMode::Code,
+            headers: Vec::new(),
         }
     }
     pub fn local_dhall_code(path: PathBuf) -> Self {
         ImportLocation {
             kind: ImportLocationKind::Local(path),
             mode: ImportMode::Code,
+            headers: Vec::new(),
+        }
+    }
+    pub fn env_dhall_code(var_name: String) -> Self {
+        ImportLocation {
+            kind: ImportLocationKind::Env(var_name),
+            mode: ImportMode::Code,
+            headers: Vec::new(),
         }
     }
     pub fn remote_dhall_code(url: Url) -> Self {
         ImportLocation {
             kind: ImportLocationKind::Remote(url),
             mode: ImportMode::Code,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Resolves symlinks in the path of a local file. Leaves the location unchanged if the file
+    /// can't be found, so that fetching it reports the error. `as Location` imports are left
+    /// unchanged too, since they must report the path as written.
+    fn canonicalize_path(self) -> ImportLocation {
+        let path = match (&self.kind, self.mode) {
+            (_, ImportMode::Location) => return self,
+            (ImportLocationKind::Local(path), _) => path,
+            _ => return self,
+        };
+        let canonical = resolve_home(path)
+            .and_then(|path| Ok(std::fs::canonicalize(path)?));
+        match canonical {
+            Ok(path) => ImportLocation {
+                kind: ImportLocationKind::Local(path),
+                mode: self.mode,
+                headers: self.headers,
+            },
+            Err(_) => self,
+        }
+    }
+
+    /// Errors if this is a local import that lies outside of `root`. Symlinks are resolved
+    /// first, so a link inside `root` can't be used to read a file outside of it.
+    fn check_root(&self, root: &Path) -> Result<(), Error> {
+        let path = match (&self.kind, self.mode) {
+            // `as Location` doesn't read the file.
+            (_, ImportMode::Location) => return Ok(()),
+            (ImportLocationKind::Local(path), _) => path,
+            _ => return Ok(()),
+        };
+        let path = env::current_dir()?.join(resolve_home(path)?);
+        let path = std::fs::canonicalize(&path)
+            .unwrap_or_else(|_| normalize_lexically(&path));
+        let root = std::fs::canonicalize(root)
+            .unwrap_or_else(|_| normalize_lexically(root));
+        if path.starts_with(&root) {
+            Ok(())
+        } else {
+            Err(ImportError::ImportOutsideRoot { path }.into())
         }
     }
 
@@ -245,19 +323,22 @@ impl ImportLocation {
         Ok(ImportLocation {
             kind,
             mode: import.mode,
+            headers: Vec::new(),
         })
     }
 
     /// Fetches the expression corresponding to this location.
     fn fetch<'cx>(
         &self,
-        env: &mut ImportEnv<'cx>,
+        env: &mut ImportEnv<'cx, '_>,
         span: Span,
     ) -> Result<Typed<'cx>, Error> {
         let cx = env.cx();
         let typed = match self.mode {
             ImportMode::Code => {
-                let parsed = self.kind.fetch_dhall()?;
+                let parsed = self
+                    .kind
+                    .fetch_dhall(&self.headers, &env.options().env_vars)?;
                 let typed = parsed.resolve_with_env(env)?.typecheck(cx)?;
                 Typed {
                     // TODO: manage to keep the Nir around. Will need fixing variables.
@@ -266,7 +347,9 @@ impl ImportLocation {
                 }
             }
             ImportMode::RawText => {
-                let text = self.kind.fetch_text()?;
+                let text = self
+                    .kind
+                    .fetch_text(&self.headers, &env.options().env_vars)?;
                 Typed {
                     hir: Hir::new(
                         HirKind::Expr(ExprKind::TextLit(text.into())),
@@ -278,32 +361,64 @@ impl ImportLocation {
             ImportMode::Location => {
                 let expr = self.kind.to_location();
                 Parsed::from_expr_without_imports(expr)
-                    .resolve(cx)
-                    .unwrap()
-                    .typecheck(cx)
-                    .unwrap()
+                    .resolve(cx)?
+                    .typecheck(cx)?
             }
         };
         Ok(typed)
     }
 }
 
+/// Reads an environment variable, preferring the values set in `ResolveOptions::env_vars`.
+fn lookup_env_var(
+    env_vars: &HashMap<String, String>,
+    name: &str,
+) -> Result<String, Error> {
+    match env_vars.get(name) {
+        Some(val) => Ok(val.clone()),
+        None => match env::var(name) {
+            Ok(val) => Ok(val),
+            Err(_) => Err(ImportError::MissingEnvVar.into()),
+        },
+    }
+}
+
 fn mkexpr(kind: UnspannedExpr) -> Expr {
     Expr::new(kind, Span::Artificial)
 }
 
-// TODO: error handling
 #[cfg(all(not(target_arch = "wasm32"), feature = "reqwest"))]
-pub(crate) fn download_http_text(url: Url) -> Result<String, Error> {
-    Ok(reqwest::blocking::get(url).unwrap().text().unwrap())
+pub(crate) fn download_http_text(
+    url: Url,
+    headers: &[(String, String)],
+) -> Result<String, Error> {
+    let remote_err = |e: reqwest::Error| ImportError::Remote(e.to_string());
+    let mut request = reqwest::blocking::Client::new().get(url);
+    for (name, value) in headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request.send().map_err(remote_err)?;
+    Ok(response.text().map_err(remote_err)?)
 }
 #[cfg(all(not(target_arch = "wasm32"), not(feature = "reqwest")))]
-pub(crate) fn download_http_text(_url: Url) -> Result<String, Error> {
-    panic!("Remote imports are disabled in this build of dhall-rust")
+pub(crate) fn download_http_text(
+    _url: Url,
+    _headers: &[(String, String)],
+) -> Result<String, Error> {
+    Err(ImportError::Remote(
+        "Remote imports are disabled in this build of dhall-rust".to_owned(),
+    )
+    .into())
 }
 #[cfg(target_arch = "wasm32")]
-pub(crate) fn download_http_text(_url: Url) -> Result<String, Error> {
-    panic!("Remote imports are not supported on wasm yet")
+pub(crate) fn download_http_text(
+    _url: Url,
+    _headers: &[(String, String)],
+) -> Result<String, Error> {
+    Err(ImportError::Remote(
+        "Remote imports are not supported on wasm yet".to_owned(),
+    )
+    .into())
 }
 
 fn make_aslocation_uniontype() -> Expr {
@@ -316,6 +431,50 @@ fn make_aslocation_uniontype() -> Expr {
     mkexpr(ExprKind::UnionType(union))
 }
 
+fn make_headers_type() -> Expr {
+    let text_type = mkexpr(ExprKind::Builtin(Builtin::Text));
+    let mut record = BTreeMap::default();
+    record.insert("mapKey".into(), text_type.clone());
+    record.insert("mapValue".into(), text_type);
+    mkexpr(ExprKind::Op(OpKind::App(
+        mkexpr(ExprKind::Builtin(Builtin::List)),
+        mkexpr(ExprKind::RecordType(record)),
+    )))
+}
+
+/// Evaluates the `using` headers of a remote import to a list of header names and values.
+fn eval_headers<'cx>(
+    cx: Ctxt<'cx>,
+    headers: &Hir<'cx>,
+) -> Result<Vec<(String, String)>, Error> {
+    let ty = Parsed::from_expr_without_imports(make_headers_type())
+        .skip_resolve(cx)?;
+    let headers = Resolved(headers.clone())
+        .typecheck_with(cx, &ty.0)?
+        .normalize(cx)
+        .to_expr(cx);
+    let entries = match headers.kind() {
+        ExprKind::NEListLit(entries) => entries,
+        ExprKind::EmptyListLit(_) => return Ok(Vec::new()),
+        _ => return Err(ImportError::InvalidHeaders.into()),
+    };
+    // The headers are closed, so their normal form should only contain literals.
+    let text = |e: &Expr| match e.kind() {
+        ExprKind::TextLit(t) if t.tail().is_empty() => Ok(t.head().to_owned()),
+        _ => Err(Error::from(ImportError::InvalidHeaders)),
+    };
+    let mut pairs = Vec::new();
+    for entry in entries {
+        match entry.kind() {
+            ExprKind::RecordLit(kvs) => {
+                pairs.push((text(&kvs["mapKey"])?, text(&kvs["mapValue"])?))
+            }
+            _ => return Err(ImportError::InvalidHeaders.into()),
+        }
+    }
+    Ok(pairs)
+}
+
 pub fn check_hash<'cx>(
     cx: Ctxt<'cx>,
     import: ImportId<'cx>,
@@ -374,13 +533,27 @@ fn desugar(expr: &Expr) -> Cow<'_, Expr> {
 
 /// Fetch the import and store the result in the global context.
 fn fetch_import<'cx>(
-    env: &mut ImportEnv<'cx>,
+    env: &mut ImportEnv<'cx, '_>,
     import_id: ImportId<'cx>,
 ) -> Result<ImportResultId<'cx>, Error> {
     let cx = env.cx();
     let import = &cx[import_id].import;
     let span = cx[import_id].span.clone();
-    let location = cx[import_id].base_location.chain(import)?;
+    let mut location = cx[import_id].base_location.chain(import)?;
+    if let Some(headers) = &cx[import_id].headers {
+        location.headers = eval_headers(cx, headers)?;
+    }
+    let location = if env.options().canonicalize_paths {
+        location.canonicalize_path()
+    } else {
+        location
+    };
+    if let Some(root) = &env.options().root_directory {
+        location.check_root(root)?;
+    }
+    if let Some(hook) = &env.options().on_import {
+        hook.call(&location);
+    }
 
     // If the hash is in the on-disk cache, return
     // the cached contents.
@@ -436,7 +609,7 @@ pub enum ImportNode<'cx> {
 /// Traverse the expression and replace each import and import alternative by an id into the global
 /// context. The ids are also accumulated into `nodes` so that we can resolve them afterwards.
 fn traverse_accumulate<'cx>(
-    env: &mut ImportEnv<'cx>,
+    env: &mut ImportEnv<'cx, '_>,
     name_env: &mut NameEnv,
     nodes: &mut Vec<ImportNode<'cx>>,
     base_location: &ImportLocation,
@@ -471,6 +644,31 @@ fn traverse_accumulate<'cx>(
             nodes.push(ImportNode::Alternative(alt));
             HirKind::ImportAlternative(alt, l, r)
         }
+        ExprKind::Import(import) => {
+            // The headers can't refer to variables in scope, but may contain imports, which must
+            // be resolved before this one.
+            let headers = match &import.location {
+                ImportTarget::Remote(URL {
+                    headers: Some(headers),
+                    ..
+                }) => Some(traverse_accumulate(
+                    env,
+                    &mut NameEnv::new(),
+                    nodes,
+                    base_location,
+                    headers,
+                )),
+                _ => None,
+            };
+            let import_id = cx.push_import(
+                base_location.clone(),
+                import.map_ref(|_| ()),
+                headers,
+                expr.span(),
+            );
+            nodes.push(ImportNode::Import(import_id));
+            HirKind::Import(import_id)
+        }
         kind => {
             let kind = kind.map_ref_maybe_binder(|l, e| {
                 if let Some(l) = l {
@@ -483,20 +681,7 @@ fn traverse_accumulate<'cx>(
                 }
                 hir
             });
-            match kind {
-                ExprKind::Import(import) => {
-                    // TODO: evaluate import headers
-                    let import = import.map_ref(|_| ());
-                    let import_id = cx.push_import(
-                        base_location.clone(),
-                        import,
-                        expr.span(),
-                    );
-                    nodes.push(ImportNode::Import(import_id));
-                    HirKind::Import(import_id)
-                }
-                kind => HirKind::Expr(kind),
-            }
+            HirKind::Expr(kind)
         }
     };
     Hir::new(kind, expr.span())
@@ -504,7 +689,7 @@ fn traverse_accumulate<'cx>(
 
 /// Take a list of nodes and recursively resolve them.
 fn resolve_nodes<'cx>(
-    env: &mut ImportEnv<'cx>,
+    env: &mut ImportEnv<'cx, '_>,
     nodes: &[ImportNode<'cx>],
 ) -> Result<(), Error> {
     for &node in nodes {
@@ -528,7 +713,7 @@ fn resolve_nodes<'cx>(
 }
 
 fn resolve_with_env<'cx>(
-    env: &mut ImportEnv<'cx>,
+    env: &mut ImportEnv<'cx, '_>,
     parsed: Parsed,
 ) -> Result<Resolved<'cx>, Error> {
     let Parsed(expr, base_location) = parsed;
@@ -552,7 +737,20 @@ pub fn resolve<'cx>(
     cx: Ctxt<'cx>,
     parsed: Parsed,
 ) -> Result<Resolved<'cx>, Error> {
-    parsed.resolve_with_env(&mut ImportEnv::new(cx))
+    resolve_with_options(cx, parsed, &ResolveOptions::default())
+}
+
+pub fn resolve_with_options<'cx>(
+    cx: Ctxt<'cx>,
+    parsed: Parsed,
+    opts: &ResolveOptions<'_>,
+) -> Result<Resolved<'cx>, Error> {
+    let parsed = if opts.canonicalize_paths {
+        Parsed(parsed.0, parsed.1.canonicalize_path())
+    } else {
+        parsed
+    };
+    parsed.resolve_with_env(&mut ImportEnv::with_options(cx, opts.clone()))
 }
 
 /// Resolves names, and errors if we find any imports.
@@ -567,7 +765,7 @@ pub fn skip_resolve<'cx>(
 impl Parsed {
     fn resolve_with_env<'cx>(
         self,
-        env: &mut ImportEnv<'cx>,
+        env: &mut ImportEnv<'cx, '_>,
     ) -> Result<Resolved<'cx>, Error> {
         resolve_with_env(env, self)
     }
@@ -617,6 +815,22 @@ impl Canonicalize for FilePath {
     }
 }
 
+/// Removes `.` and `..` components without touching the filesystem. Used for files that don't
+/// exist, since `fs::canonicalize` fails on those.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub(crate) fn resolve_home(path: impl AsRef<Path>) -> Result<PathBuf, Error> {
     let mut f = PathBuf::new();
@@ -638,7 +852,8 @@ pub(crate) fn resolve_home(path: impl AsRef<Path>) -> Result<PathBuf, Error> {
 
 #[cfg(target_arch = "wasm32")]
 pub(crate) fn resolve_home(_path: impl AsRef<Path>) -> Result<PathBuf, Error> {
-    panic!("Imports relative to home are not supported on wasm yet");
+    // There is no home directory on wasm.
+    Err(ImportError::MissingHome.into())
 }
 
 impl<SE: Copy> Canonicalize for ImportTarget<SE> {
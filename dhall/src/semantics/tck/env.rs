@@ -14,6 +14,8 @@ pub struct TyEnv<'cx> {
     cx: Ctxt<'cx>,
     names: NameEnv,
     items: ValEnv<'cx, Type<'cx>>,
+    /// Whether to keep going after an error in a record or list literal.
+    collect_errors: bool,
 }
 
 impl VarEnv {
@@ -46,8 +48,18 @@ impl<'cx> TyEnv<'cx> {
             cx,
             names: NameEnv::new(),
             items: ValEnv::new(cx),
+            collect_errors: false,
         }
     }
+    pub fn collecting_errors(self, collect_errors: bool) -> Self {
+        TyEnv {
+            collect_errors,
+            ..self
+        }
+    }
+    pub fn collects_errors(&self) -> bool {
+        self.collect_errors
+    }
     pub fn cx(&self) -> Ctxt<'cx> {
         self.cx
     }
@@ -65,14 +77,16 @@ impl<'cx> TyEnv<'cx> {
         TyEnv {
             cx: self.cx,
             names: self.names.insert(x),
-            items: self.items.insert_type(ty),
+            items: self.items.insert_type(x, ty),
+            collect_errors: self.collect_errors,
         }
     }
     pub fn insert_value(&self, x: &Label, e: Nir<'cx>, ty: Type<'cx>) -> Self {
         TyEnv {
             cx: self.cx,
             names: self.names.insert(x),
-            items: self.items.insert_value(e, ty),
+            items: self.items.insert_value(x, e, ty),
+            collect_errors: self.collect_errors,
         }
     }
     pub fn lookup(&self, var: AlphaVar) -> Type<'cx> {
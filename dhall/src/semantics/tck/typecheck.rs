@@ -4,7 +4,9 @@ use crate::builtins::{type_of_builtin, Builtin};
 use crate::error::{ErrorBuilder, TypeError, TypeMessage};
 use crate::operations::typecheck_operation;
 use crate::semantics::{Hir, HirKind, Nir, NirKind, Tir, TyEnv, Type};
-use crate::syntax::{Const, ExprKind, InterpolatedTextContents, NumKind, Span};
+use crate::syntax::{
+    Const, ExprKind, InterpolatedTextContents, Label, NumKind, Span, V,
+};
 use crate::Ctxt;
 
 fn function_check(a: Const, b: Const) -> Const {
@@ -165,7 +167,22 @@ fn type_one_layer<'cx>(
             let t = t.eval_to_type(env)?;
             match t.kind() {
                 NirKind::Equivalence(x, y) if x == y => {}
-                NirKind::Equivalence(..) => return span_err("AssertMismatch"),
+                NirKind::Equivalence(x, y) => {
+                    // Show both normalized sides to make the failure debuggable.
+                    return mkerr(
+                        ErrorBuilder::new("AssertMismatch")
+                            .span_err(span, "AssertMismatch")
+                            .note(format!(
+                                "the left-hand side normalizes to: {}",
+                                x.to_expr_tyenv(env)
+                            ))
+                            .note(format!(
+                                "the right-hand side normalizes to: {}",
+                                y.to_expr_tyenv(env)
+                            ))
+                            .format(),
+                    );
+                }
                 _ => return span_err("AssertMustTakeEquivalence"),
             }
             t
@@ -173,6 +190,92 @@ fn type_one_layer<'cx>(
     })
 }
 
+/// Typechecks the subexpressions of a record or list literal, continuing past errors so that all
+/// the independent errors can be reported together. When an annotation is known, it is checked
+/// against each field or element separately.
+fn type_subexprs_collecting_errors<'cx, 'hir>(
+    env: &TyEnv<'cx>,
+    ekind: &'hir ExprKind<Hir<'cx>>,
+    annot: Option<&Type<'cx>>,
+) -> Result<ExprKind<Tir<'cx, 'hir>>, TypeError> {
+    let mut errors = Vec::new();
+    let mut type_child = |e, annot: Option<&Nir<'cx>>| {
+        let annot = annot
+            .map(|t| Type::new_infer_universe(env, t.clone()))
+            .transpose()?;
+        Ok(type_with(env, e, annot).map_err(|e| errors.push(e)).ok())
+    };
+
+    let ekind = match ekind {
+        ExprKind::RecordLit(kvs) => {
+            let kts = match annot.map(|t| t.kind()) {
+                Some(NirKind::RecordType(kts)) => Some(kts),
+                _ => None,
+            };
+            let mut typed = Vec::new();
+            for (k, v) in kvs {
+                let field_annot = kts.and_then(|kts| kts.get(k));
+                if let Some(v) = type_child(v, field_annot)? {
+                    typed.push((k.clone(), v));
+                }
+            }
+            ExprKind::RecordLit(typed.into_iter().collect())
+        }
+        ExprKind::NEListLit(xs) => {
+            let t = match annot.map(|t| t.kind()) {
+                Some(NirKind::ListType(t)) => Some(t),
+                _ => None,
+            };
+            let mut typed = Vec::new();
+            for x in xs {
+                if let Some(x) = type_child(x, t)? {
+                    typed.push(x);
+                }
+            }
+            ExprKind::NEListLit(typed)
+        }
+        ekind => ekind.traverse_ref(|e| type_with(env, e, None))?,
+    };
+
+    if errors.is_empty() {
+        Ok(ekind)
+    } else {
+        Err(TypeError::multiple(errors))
+    }
+}
+
+/// Finds a use of the variable `name` that couldn't be resolved, e.g. in the value of a `let`
+/// binding that refers to itself.
+fn find_missing_var(hir: &Hir<'_>, name: &Label) -> Option<Span> {
+    match hir.kind() {
+        HirKind::MissingVar(V(l, _)) if l == name => Some(hir.span()),
+        HirKind::ImportAlternative(_, left, right) => {
+            find_missing_var(left, name)
+                .or_else(|| find_missing_var(right, name))
+        }
+        HirKind::Expr(e) => e
+            .traverse_ref(|x| match find_missing_var(x, name) {
+                Some(span) => Err(span),
+                None => Ok(()),
+            })
+            .err(),
+        _ => None,
+    }
+}
+
+/// The span of the annotation `annot` of `hir`, unless they come from the same source text, as
+/// happens for the desugaring of record completion.
+fn distinct_span(hir: &Hir<'_>, annot: &Hir<'_>) -> Option<Span> {
+    match (hir.span(), annot.span()) {
+        (Span::Parsed(x), Span::Parsed(t))
+            if x.as_char_range() == t.as_char_range() =>
+        {
+            None
+        }
+        (_, span) => Some(span),
+    }
+}
+
 /// `type_with` typechecks an expression in the provided environment. Optionally pass an annotation
 /// to compare with.
 // We pass the annotation to avoid duplicating the annot checking logic. I hope one day we can use
@@ -181,6 +284,16 @@ pub fn type_with<'cx, 'hir>(
     env: &TyEnv<'cx>,
     hir: &'hir Hir<'cx>,
     annot: Option<Type<'cx>>,
+) -> Result<Tir<'cx, 'hir>, TypeError> {
+    type_with_annot_span(env, hir, annot, None)
+}
+
+/// Like `type_with`, but also knows where the annotation was written, to point at it on mismatch.
+fn type_with_annot_span<'cx, 'hir>(
+    env: &TyEnv<'cx>,
+    hir: &'hir Hir<'cx>,
+    annot: Option<Type<'cx>>,
+    annot_span: Option<Span>,
 ) -> Result<Tir<'cx, 'hir>, TypeError> {
     let tir = match hir.kind() {
         HirKind::Var(var) => Tir::from_hir(hir, env.lookup(*var)),
@@ -199,7 +312,7 @@ pub fn type_with<'cx, 'hir>(
             } else {
                 right
             };
-            return type_with(env, hir, annot);
+            return type_with_annot_span(env, hir, annot, annot_span);
         }
         HirKind::Expr(ExprKind::Var(_)) => {
             unreachable!("Hir should contain no unresolved variables")
@@ -208,13 +321,14 @@ pub fn type_with<'cx, 'hir>(
             return mk_span_err(hir.span(), "Sort does not have a type")
         }
         HirKind::Expr(ExprKind::Annot(x, t)) => {
+            let t_span = distinct_span(x, t);
             let t = match t.kind() {
                 HirKind::Expr(ExprKind::Const(Const::Sort)) => {
                     Type::from_const(Const::Sort)
                 }
                 _ => type_with(env, t, None)?.eval_to_type(env)?,
             };
-            type_with(env, x, Some(t))?
+            type_with_annot_span(env, x, Some(t), t_span)?
         }
 
         HirKind::Expr(ExprKind::Lam(binder, annot, body)) => {
@@ -254,11 +368,32 @@ pub fn type_with<'cx, 'hir>(
             Tir::from_hir(hir, ty)
         }
         HirKind::Expr(ExprKind::Let(binder, annot, val, body)) => {
+            if let Some(span) = find_missing_var(val, binder) {
+                mkerr(
+                    ErrorBuilder::new(format!(
+                        "recursive binding `{}`",
+                        binder
+                    ))
+                    .span_err(span, "this refers to the binding being defined")
+                    .span_help(
+                        val.span(),
+                        format!("in the definition of `{}`", binder),
+                    )
+                    .help(
+                        "Dhall doesn't support recursion; a `let` binding is only in scope in \
+                         the expression after `in`",
+                    )
+                    .format(),
+                )?
+            }
             let val_annot = annot
                 .as_ref()
                 .map(|t| type_with(env, t, None)?.eval_to_type(env))
                 .transpose()?;
-            let val = type_with(env, &val, val_annot)?;
+            let val_annot_span =
+                annot.as_ref().and_then(|t| distinct_span(val, t));
+            let val =
+                type_with_annot_span(env, &val, val_annot, val_annot_span)?;
             let val_nf = val.eval(env);
             let body_env = env.insert_value(&binder, val_nf, val.ty().clone());
             let body = type_with(&body_env, body, None)?;
@@ -266,28 +401,47 @@ pub fn type_with<'cx, 'hir>(
             Tir::from_hir(hir, ty)
         }
         HirKind::Expr(ekind) => {
-            let ekind = ekind.traverse_ref(|e| type_with(env, e, None))?;
+            let ekind = if env.collects_errors() {
+                type_subexprs_collecting_errors(env, ekind, annot.as_ref())?
+            } else {
+                ekind.traverse_ref(|e| type_with(env, e, None))?
+            };
             let ty = type_one_layer(env, ekind, hir.span())?;
             Tir::from_hir(hir, ty)
         }
     };
 
     if let Some(annot) = annot {
-        if *tir.ty() != annot {
-            return mk_span_err(
-                hir.span(),
-                &format!(
-                    "annot mismatch: {} != {}",
-                    tir.ty().to_expr_tyenv(env),
-                    annot.to_expr_tyenv(env)
-                ),
-            );
-        }
+        check_annot(env, hir, tir.ty(), &annot, annot_span)?;
     }
 
     Ok(tir)
 }
 
+/// Checks that an expression of type `ty` matches its annotation.
+fn check_annot<'cx>(
+    env: &TyEnv<'cx>,
+    hir: &Hir<'cx>,
+    ty: &Type<'cx>,
+    annot: &Type<'cx>,
+    annot_span: Option<Span>,
+) -> Result<(), TypeError> {
+    if ty != annot {
+        let ty = ty.to_expr_tyenv(env);
+        let annot = annot.to_expr_tyenv(env);
+        let mut err = ErrorBuilder::new(format!(
+            "expression has type `{}` but was annotated with `{}`",
+            ty, annot
+        ));
+        err.span_err(hir.span(), format!("this has type `{}`", ty));
+        if let Some(span) = annot_span {
+            err.span_help(span, format!("the annotation is `{}`", annot));
+        }
+        return mkerr(err.format());
+    }
+    Ok(())
+}
+
 /// Typecheck an expression and return the expression annotated with its type if type-checking
 /// succeeded, or an error if type-checking failed.
 pub fn typecheck<'cx, 'hir>(
@@ -306,3 +460,30 @@ pub fn typecheck_with<'cx, 'hir>(
     let ty = typecheck(cx, ty)?.eval_to_type(&TyEnv::new(cx))?;
     type_with(&TyEnv::new(cx), hir, Some(ty))
 }
+
+/// Checks that a closed expression that was already typechecked with type `hir_ty` has the
+/// provided type, with the same error as `typecheck_with`.
+pub fn check_type<'cx>(
+    cx: Ctxt<'cx>,
+    hir: &Hir<'cx>,
+    hir_ty: &Type<'cx>,
+    ty: &Hir<'cx>,
+) -> Result<(), TypeError> {
+    let env = TyEnv::new(cx);
+    let ty = typecheck(cx, ty)?.eval_to_type(&env)?;
+    check_annot(&env, hir, hir_ty, &ty, None)
+}
+
+/// Like `typecheck_with`, but keeps going after an error in a record or list literal and returns
+/// all the errors found.
+pub fn typecheck_collecting_errors<'cx, 'hir>(
+    cx: Ctxt<'cx>,
+    hir: &'hir Hir<'cx>,
+    ty: Option<&Hir<'cx>>,
+) -> Result<Tir<'cx, 'hir>, TypeError> {
+    let env = TyEnv::new(cx).collecting_errors(true);
+    let ty = ty
+        .map(|ty| typecheck(cx, ty)?.eval_to_type(&env))
+        .transpose()?;
+    type_with(&env, hir, ty)
+}
@@ -1,8 +1,8 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 
 use crate::builtins::Builtin;
 use crate::error::Error;
-use crate::operations::OpKind;
+use crate::operations::{BinOp, OpKind};
 use crate::semantics::Universe;
 use crate::syntax::visitor;
 use crate::syntax::*;
@@ -187,6 +187,104 @@ impl Expr {
     pub fn add_let_binding(self, label: Label, value: Expr) -> Expr {
         Expr::new(ExprKind::Let(label, None, value, self), Span::Artificial)
     }
+
+    /// Merge the expression over `defaults` with the `//` operator
+    pub fn prefer_over(self, defaults: Expr) -> Expr {
+        Expr::new(
+            ExprKind::Op(OpKind::BinOp(
+                BinOp::RightBiasedRecordMerge,
+                defaults,
+                self,
+            )),
+            Span::Artificial,
+        )
+    }
+
+    /// The names of the variables that occur free in the expression, i.e. that aren't bound by
+    /// an enclosing `λ`, `∀` or `let`. Imports are not looked into.
+    pub fn free_vars(&self) -> HashSet<Label> {
+        let mut vars = HashSet::new();
+        self.collect_free_vars(&mut Vec::new(), &mut vars);
+        vars
+    }
+
+    /// Whether the expression has no free variables.
+    pub fn is_closed(&self) -> bool {
+        self.free_vars().is_empty()
+    }
+
+    /// Compares two expressions ignoring type annotations `x : T`. Like `==`, this also ignores
+    /// source spans. This is useful to compare source-level structure before normalization, which
+    /// would remove annotations anyway.
+    pub fn structural_eq(&self, other: &Expr) -> bool {
+        self.strip_annots() == other.strip_annots()
+    }
+
+    /// The number of nodes in the syntax tree of the expression, counting this one. Imports are
+    /// not looked into. This measures the size of an expression before normalizing it.
+    pub fn count_nodes(&self) -> usize {
+        let mut count = 1;
+        self.kind().map_ref(|e| count += e.count_nodes());
+        count
+    }
+
+    /// The length of the longest path from this node to a leaf of the syntax tree, counting both
+    /// ends; a leaf has depth 1. Imports are not looked into.
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        self.kind().map_ref(|e| depth = depth.max(e.depth()));
+        depth + 1
+    }
+
+    /// Rewrites every import of the expression with `f`, without resolving them, e.g. to point
+    /// imports from one host to a mirror. The imports in the `using` headers of a remote import
+    /// are rewritten before the import itself.
+    pub fn map_imports(
+        &self,
+        f: impl Fn(Import<Expr>) -> Import<Expr>,
+    ) -> Expr {
+        self.map_imports_with(&f)
+    }
+
+    fn map_imports_with(
+        &self,
+        f: &dyn Fn(Import<Expr>) -> Import<Expr>,
+    ) -> Expr {
+        let kind = match self.kind().map_ref(|e| e.map_imports_with(f)) {
+            ExprKind::Import(import) => ExprKind::Import(f(import)),
+            kind => kind,
+        };
+        Expr::new(kind, self.span())
+    }
+
+    fn strip_annots(&self) -> Expr {
+        match self.kind() {
+            ExprKind::Annot(x, _) => x.strip_annots(),
+            kind => Expr::new(kind.map_ref(Expr::strip_annots), self.span()),
+        }
+    }
+
+    fn collect_free_vars<'a>(
+        &'a self,
+        bound: &mut Vec<&'a Label>,
+        vars: &mut HashSet<Label>,
+    ) {
+        if let ExprKind::Var(V(x, n)) = self.kind() {
+            // `x@n` refers to the `n`-th enclosing binder named `x`.
+            if bound.iter().filter(|y| **y == x).count() <= *n {
+                vars.insert(x.clone());
+            }
+            return;
+        }
+        self.kind().map_ref_maybe_binder(|l, e| match l {
+            Some(l) => {
+                bound.push(l);
+                e.collect_free_vars(bound, vars);
+                bound.pop();
+            }
+            None => e.collect_free_vars(bound, vars),
+        });
+    }
 }
 
 // Empty enum to indicate that no error can occur
@@ -13,6 +13,7 @@ pub enum FilePrefix {
     Home,
 }
 
+/// The components of a file path, without its prefix
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FilePath {
     pub file_path: Vec<String>,
@@ -21,18 +22,24 @@ pub struct FilePath {
 /// The location of import (i.e. local vs. remote vs. environment)
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ImportTarget<SubExpr> {
+    /// A file path, e.g. `./foo/bar.dhall`
     Local(FilePrefix, FilePath),
+    /// A url, e.g. `https://example.com/foo.dhall`
     Remote(URL<SubExpr>),
+    /// An environment variable, e.g. `env:HOME`
     Env(String),
+    /// The `missing` import
     Missing,
 }
 
+/// A remote import target
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct URL<SubExpr> {
     pub scheme: Scheme,
     pub authority: String,
     pub path: FilePath,
     pub query: Option<String>,
+    /// The expression given with `using`, if any
     pub headers: Option<SubExpr>,
 }
 
@@ -45,11 +52,15 @@ pub enum Scheme {
 /// How to interpret the import's contents (i.e. as Dhall code or raw text)
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum ImportMode {
+    /// A plain import, whose contents are parsed as Dhall code
     Code,
+    /// `as Text`
     RawText,
+    /// `as Location`
     Location,
 }
 
+/// An integrity check, e.g. `sha256:...`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Hash {
     SHA256(Box<[u8]>),
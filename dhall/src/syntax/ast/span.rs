@@ -1,3 +1,4 @@
+use std::ops::Range;
 use std::rc::Rc;
 
 /// A location in the source text
@@ -32,6 +33,10 @@ impl ParsedSpan {
     pub fn to_input(&self) -> String {
         self.input.to_string()
     }
+    /// The byte range of the input that the span covers.
+    pub(crate) fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
     /// Convert to a char range for consumption by annotate_snippets.
     /// This compensates for  https://github.com/rust-lang/annotate-snippets-rs/issues/24
     pub fn as_char_range(&self) -> (usize, usize) {
@@ -16,11 +16,35 @@ type DecodedExpr = Expr;
 
 pub fn decode(data: &[u8]) -> Result<DecodedExpr, DecodeError> {
     match serde_cbor::de::from_slice(data) {
-        Ok(v) => cbor_value_to_dhall(&v),
+        Ok(v) => {
+            check_version(&v)?;
+            cbor_value_to_dhall(&v)
+        }
         Err(e) => Err(DecodeError::CBORError(e)),
     }
 }
 
+/// Older versions of the standard wrapped the encoded expression as `["x.y.z", expr]`. This is
+/// no longer supported; detect it to give a clear error instead of a confusing decode failure.
+/// A version tag followed by a natural number is indistinguishable from a variable, so it is
+/// left to decode as such.
+fn check_version(data: &Value) -> Result<(), DecodeError> {
+    if let Value::Array(vec) = data {
+        if let [Value::String(version), expr] = vec.as_slice() {
+            let looks_like_version = version.split('.').count() == 3
+                && version.split('.').all(|n| {
+                    !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit())
+                });
+            if looks_like_version && !matches!(expr, Value::U64(_)) {
+                return Err(DecodeError::UnsupportedBinaryVersion {
+                    found: version.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
 /// An enum that can encode most CBOR values.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value {
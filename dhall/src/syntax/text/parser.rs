@@ -3,6 +3,7 @@ use pest::prec_climber as pcl;
 use pest::prec_climber::PrecClimber;
 use std::collections::{BTreeMap, BTreeSet};
 use std::iter::once;
+use std::ops::Range;
 use std::rc::Rc;
 
 use pest_consume::{match_nodes, Parser};
@@ -46,7 +47,9 @@ fn spanned_union(span1: Span, span2: Span, x: UnspannedExpr) -> Expr {
 }
 
 // Trim the shared indent off of a vec of lines, as defined by the Dhall semantics of multiline
-// literals.
+// literals. The indent is the longest common prefix of spaces and tabs of all the non-empty lines,
+// plus the last line (the one with the closing quotes) even if it is empty. Tabs and spaces are
+// compared literally.
 fn trim_indent(lines: &mut Vec<ParsedText>) {
     let is_indent = |c: char| c == ' ' || c == '\t';
 
@@ -260,7 +263,15 @@ impl DhallParser {
                     }
                     _ => {}
                 }
-                let c: char = i.try_into().unwrap();
+                let c: char = match i.try_into() {
+                    Ok(c) => c,
+                    Err(_) => {
+                        return Err(input.error(
+                            "Escape sequence is not a valid code point"
+                                .to_string(),
+                        ))
+                    }
+                };
                 std::iter::once(c).collect()
             }
         })
@@ -1036,6 +1047,150 @@ pub fn parse_expr(input_str: &str) -> ParseResult<Expr> {
     ))
 }
 
+/// Parses the input like `parse_expr`, and also returns the byte ranges of the comments and
+/// shebang lines found in it, in order.
+pub(crate) fn parse_expr_with_comments(
+    input_str: &str,
+) -> ParseResult<(Expr, Vec<Range<usize>>)> {
+    let rc_input_str = input_str.to_string().into();
+    let inputs = DhallParser::parse_with_userdata(
+        Rule::final_expression,
+        input_str,
+        rc_input_str,
+    )?;
+    let mut comments = Vec::new();
+    for node in inputs.clone() {
+        find_comments(node.as_pair().clone(), input_str, &mut comments);
+    }
+    let expr = match_nodes!(<DhallParser>; inputs;
+        [expression(e)] => e,
+    );
+    Ok((expr, comments))
+}
+
+/// Comments are silent in the grammar, so they can only be in the text of a node that isn't
+/// covered by its children. The leaves listed here are the ones whose text can look like a
+/// comment without being one.
+fn find_comments(
+    pair: pest::iterators::Pair<Rule>,
+    input: &str,
+    comments: &mut Vec<Range<usize>>,
+) {
+    use Rule::*;
+    let rule = pair.as_rule();
+    let span = pair.as_span();
+    let mut children = pair.into_inner().peekable();
+    if children.peek().is_none() {
+        match rule {
+            double_quote_char
+            | double_quote_escaped
+            | single_quote_char
+            | escaped_quote_pair
+            | escaped_interpolation
+            | simple_label
+            | quoted_label
+            | unquoted_path_component
+            | quoted_path_component
+            | scheme
+            | authority
+            | segment
+            | query
+            | bash_environment_variable
+            | posix_environment_variable_character
+            | hash => {}
+            _ => scan_comments(input, span.start()..span.end(), comments),
+        }
+        return;
+    }
+    let mut pos = span.start();
+    for child in children {
+        let child_span = child.as_span();
+        scan_comments(input, pos..child_span.start(), comments);
+        pos = child_span.end();
+        find_comments(child, input, comments);
+    }
+    scan_comments(input, pos..span.end(), comments);
+}
+
+/// Finds the comments in a piece of input that only contains keywords, punctuation, whitespace
+/// and comments.
+fn scan_comments(
+    input: &str,
+    range: Range<usize>,
+    comments: &mut Vec<Range<usize>>,
+) {
+    let bytes = input.as_bytes();
+    let mut i = range.start;
+    while i + 1 < range.end {
+        let start = i;
+        match &bytes[i..i + 2] {
+            b"--" | b"#!" => {
+                i = input[i..range.end].find('\n').map_or(range.end, |n| i + n);
+            }
+            b"{-" => {
+                let mut depth = 0;
+                while i + 1 < range.end {
+                    match &bytes[i..i + 2] {
+                        b"{-" => depth += 1,
+                        b"-}" => depth -= 1,
+                        _ => {
+                            i += 1;
+                            continue;
+                        }
+                    }
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        comments.push(start..i);
+    }
+}
+
+/// Makes an error that points at the given byte offset of the input.
+pub(crate) fn error_at(input: &str, pos: usize, message: String) -> ParseError {
+    ParseError::new_from_pos(
+        pest::error::ErrorVariant::CustomError { message },
+        pest::Position::new(input, pos).unwrap(),
+    )
+}
+
+/// Parses the input and returns the raw parse tree as JSON, without building an expression.
+/// This is useful to debug the grammar, or for tools that need the concrete syntax.
+///
+/// Each node is an object with the name of its grammar `rule`, the byte offsets `start` and
+/// `end` of its span, its `text` and its `children`. Rules that the grammar marks as silent, like
+/// whitespace, don't appear.
+#[cfg(feature = "parse-tree-json")]
+pub fn parse_tree_json(input_str: &str) -> ParseResult<serde_json::Value> {
+    let rc_input_str: Rc<str> = input_str.to_string().into();
+    let node = DhallParser::parse_with_userdata(
+        Rule::final_expression,
+        input_str,
+        rc_input_str,
+    )?
+    .single()?;
+    Ok(pair_to_json(node.as_pair().clone()))
+}
+
+#[cfg(feature = "parse-tree-json")]
+fn pair_to_json(pair: pest::iterators::Pair<Rule>) -> serde_json::Value {
+    let span = pair.as_span();
+    serde_json::json!({
+        "rule": format!("{:?}", pair.as_rule()),
+        "start": span.start(),
+        "end": span.end(),
+        "text": pair.as_str(),
+        "children": pair.into_inner().map(pair_to_json).collect::<Vec<_>>(),
+    })
+}
+
 #[test]
 #[cfg_attr(windows, ignore)]
 // Check that the local copy of the grammar file is in sync with the one from dhall-lang.
@@ -1065,3 +1220,96 @@ fn test_grammar_files_in_sync() {
         );
     }
 }
+
+#[test]
+fn test_multiline_dedent() {
+    let text = |s: &str| match parse_expr(s).unwrap().kind() {
+        TextLit(t) => t.clone(),
+        e => panic!("not a text literal: {:?}", e),
+    };
+    let plain = |s: &str| ParsedText::from(s.to_string());
+
+    assert_eq!(
+        text("''\n    foo\n      bar\n    ''"),
+        plain("foo\n  bar\n")
+    );
+    // A blank first line doesn't count towards the indentation.
+    assert_eq!(text("''\n\n  foo\n  bar''"), plain("\nfoo\nbar"));
+    // The last line always counts, even if it only contains whitespace.
+    assert_eq!(text("''\n    foo\n  ''"), plain("  foo\n"));
+    assert_eq!(text("''\n  foo\n''"), plain("  foo\n"));
+    // Whitespace-only lines that aren't empty do count.
+    assert_eq!(text("''\n    foo\n  \n    ''"), plain("  foo\n\n  "));
+    // Tabs and spaces are not interchangeable.
+    assert_eq!(text("''\n\tfoo\n  bar\n''"), plain("\tfoo\n  bar\n"));
+    assert_eq!(text("''\n\t foo\n\t bar\n\t ''"), plain("foo\nbar\n"));
+    // Windows line endings are normalized.
+    assert_eq!(text("''\r\n  foo\r\n  ''"), plain("foo\n"));
+    // Interpolations stop the indentation.
+    let e = parse_expr("x").unwrap();
+    assert_eq!(
+        text("''\n  ${x}\n   foo\n  ''"),
+        ParsedText::from(("".to_string(), vec![(e, "\n foo\n".to_string())]))
+    );
+}
+
+#[test]
+fn test_record_pun() {
+    let parse = |s: &str| parse_expr(s).unwrap();
+    assert_eq!(parse("{ x }"), parse("{ x = x }"));
+    assert_eq!(
+        parse("{ x, y.z = 1, w }"),
+        parse("{ x = x, y.z = 1, w = w }")
+    );
+    assert_eq!(parse("let x = 1 in { x }"), parse("let x = 1 in { x = x }"));
+}
+
+#[test]
+fn test_comments_between_tokens() {
+    let parse = |s: &str| parse_expr(s).unwrap();
+    let c = "{- a {- nested -} comment -}";
+    // The leading space avoids `{--`, which starts a block comment.
+    let l = " -- a line comment\n";
+    for sep in &[c, l] {
+        // Put a comment between every two tokens.
+        let record =
+            ["{", "x", "=", "1", "+", "2", ",", "y.z", "=", "True", "}"];
+        assert_eq!(
+            parse(&record.join(sep)),
+            parse("{ x = 1 + 2, y.z = True }"),
+            "{}",
+            sep
+        );
+        let record =
+            ["{", "x", ":", "Natural", ",", "y", ":", "List", "Bool", "}"];
+        assert_eq!(
+            parse(&record.join(sep)),
+            parse("{ x : Natural, y : List Bool }")
+        );
+        let lets = [
+            "let", "x", ":", "Natural", "=", "1", "let", "y", "=", "x", "in",
+            "x", "*", "y",
+        ];
+        assert_eq!(
+            parse(&lets.join(sep)),
+            parse("let x : Natural = 1 let y = x in x * y")
+        );
+    }
+    assert_eq!(parse("{- {- -} -} 1 -- end"), parse("1"));
+    assert_eq!(parse("1 -- end\r\n"), parse("1"));
+}
+
+#[test]
+fn test_hex_literals() {
+    let parse = |s: &str| parse_expr(s).unwrap();
+    assert_eq!(parse("0xFF"), parse("255"));
+    assert_eq!(parse("0xff"), parse("255"));
+    assert_eq!(parse("+0x10"), parse("+16"));
+    assert_eq!(parse("-0x10"), parse("-16"));
+    assert_eq!(parse("x@0x1"), parse("x@1"));
+    assert!(parse_expr("0xG").is_err());
+    assert!(parse_expr("0x").is_err());
+    assert!(parse_expr("0x10000000000000000").is_err());
+    assert!(parse_expr("+0x8000000000000000").is_err());
+    assert_eq!(parse("-0x8000000000000000"), parse("-9223372036854775808"));
+}
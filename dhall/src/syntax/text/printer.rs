@@ -10,7 +10,7 @@ use std::fmt::{self, Display};
 // of automatically getting all the parentheses and precedences right (in a manner dual do Pratt
 // parsing).
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
-enum PrintPhase {
+pub(crate) enum PrintPhase {
     // `expression`
     Base,
     // `operator-expression`
@@ -25,10 +25,33 @@ enum PrintPhase {
     Primitive,
 }
 
+impl PrintPhase {
+    // The phase of the right operand of a binary operator. Since operators are left-associative,
+    // this is the phase of the next operator in precedence order.
+    fn binop_rhs(op: BinOp) -> PrintPhase {
+        use self::BinOp::*;
+        PrintPhase::BinOp(match op {
+            Equivalence => ImportAlt,
+            ImportAlt => BoolOr,
+            BoolOr => NaturalPlus,
+            NaturalPlus => TextAppend,
+            TextAppend => ListAppend,
+            ListAppend => BoolAnd,
+            BoolAnd => RecursiveRecordMerge,
+            RecursiveRecordMerge => RightBiasedRecordMerge,
+            RightBiasedRecordMerge => RecursiveRecordTypeMerge,
+            RecursiveRecordTypeMerge => NaturalTimes,
+            NaturalTimes => BoolEQ,
+            BoolEQ => BoolNE,
+            BoolNE => return PrintPhase::App,
+        })
+    }
+}
+
 // Wraps an Expr with a phase, so that phase selection can be done separate from the actual
 // printing.
 #[derive(Copy, Clone)]
-struct PhasedExpr<'a>(&'a Expr, PrintPhase);
+pub(crate) struct PhasedExpr<'a>(pub(crate) &'a Expr, pub(crate) PrintPhase);
 
 impl<'a> PhasedExpr<'a> {
     fn phase(self, phase: PrintPhase) -> PhasedExpr<'a> {
@@ -38,7 +61,7 @@ impl<'a> PhasedExpr<'a> {
 
 impl UnspannedExpr {
     // Annotate subexpressions with the appropriate phase, defaulting to Base
-    fn annotate_with_phases(&self) -> ExprKind<PhasedExpr<'_>> {
+    pub(crate) fn annotate_with_phases(&self) -> ExprKind<PhasedExpr<'_>> {
         use ExprKind::*;
         use OpKind::*;
         use PrintPhase::*;
@@ -61,10 +84,12 @@ impl UnspannedExpr {
                 b.map(|x| x.phase(PrintPhase::App)),
             )),
             Annot(a, b) => Annot(a.phase(Operator), b),
+            // Operators are left-associative, so a right operand of the same precedence needs
+            // parentheses to round-trip.
             Op(OpKind::BinOp(op, a, b)) => Op(OpKind::BinOp(
                 op,
                 a.phase(PrintPhase::BinOp(op)),
-                b.phase(PrintPhase::BinOp(op)),
+                b.phase(PrintPhase::binop_rhs(op)),
             )),
             SomeLit(e) => SomeLit(e.phase(PrintPhase::Import)),
             Op(OpKind::App(f, a)) => Op(OpKind::App(
@@ -79,6 +104,9 @@ impl UnspannedExpr {
             Op(Completion(a, b)) => {
                 Op(Completion(a.phase(Primitive), b.phase(Primitive)))
             }
+            Op(With(a, ls, b)) => {
+                Op(With(a.phase(PrintPhase::Import), ls, b.phase(Operator)))
+            }
             ExprKind::Import(a) => {
                 ExprKind::Import(a.map_ref(|x| x.phase(PrintPhase::Import)))
             }
@@ -86,15 +114,11 @@ impl UnspannedExpr {
         }
     }
 
-    fn fmt_phase(
-        &self,
-        f: &mut fmt::Formatter,
-        phase: PrintPhase,
-    ) -> Result<(), fmt::Error> {
+    // Whether the expression must be wrapped in parentheses to be printed in the given phase.
+    pub(crate) fn needs_paren(&self, phase: PrintPhase) -> bool {
         use ExprKind::*;
         use OpKind::*;
-
-        let needs_paren = match self {
+        match self {
             Lam(_, _, _)
             | Pi(_, _, _)
             | Let(_, _, _, _)
@@ -103,6 +127,7 @@ impl UnspannedExpr {
             | Op(BoolIf(_, _, _))
             | Op(Merge(_, _, _))
             | Op(ToMap(_, _))
+            | Op(With(_, _, _))
             | Annot(_, _) => phase > PrintPhase::Base,
             // Precedence is magically handled by the ordering of BinOps. This is reverse Pratt
             // parsing.
@@ -110,8 +135,15 @@ impl UnspannedExpr {
             Op(App(_, _)) => phase > PrintPhase::App,
             Op(Completion(_, _)) => phase > PrintPhase::Import,
             _ => false,
-        };
+        }
+    }
 
+    fn fmt_phase(
+        &self,
+        f: &mut fmt::Formatter,
+        phase: PrintPhase,
+    ) -> Result<(), fmt::Error> {
+        let needs_paren = self.needs_paren(phase);
         if needs_paren {
             f.write_str("(")?;
         }
@@ -157,6 +189,7 @@ fn fmt_label(label: &Label, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
     if s.is_empty() {
         write!(f, "``")
     } else if !is_reserved
+        && !s.starts_with(|c: char| c.is_ascii_digit())
         && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
     {
         write!(f, "{}", s)
@@ -165,6 +198,15 @@ fn fmt_label(label: &Label, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
     }
 }
 
+/// Prints a label, quoting it with backticks if needed.
+pub struct QuotedLabel<'a>(pub &'a Label);
+
+impl Display for QuotedLabel<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt_label(self.0, f)
+    }
+}
+
 /// Generic instance that delegates to subexpressions
 impl<SE: Display + Clone> Display for ExprKind<SE> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
@@ -286,6 +328,8 @@ impl<SE: Display + Clone> Display for OpKind<SE> {
     }
 }
 
+/// Prints the expression as valid Dhall source, inserting only the parentheses needed for it to
+/// parse back to the same expression.
 impl Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         self.kind().fmt_phase(f, PrintPhase::Base)
@@ -522,3 +566,24 @@ impl Display for V {
         Ok(())
     }
 }
+
+#[test]
+fn test_print_minimal_parens() {
+    let roundtrip = |s: &str| {
+        let e = parse_expr(s).unwrap();
+        let printed = e.to_string();
+        assert_eq!(parse_expr(&printed).unwrap(), e);
+        printed
+    };
+
+    assert_eq!(roundtrip("(1 + 2) * 3"), "(1 + 2) * 3");
+    assert_eq!(roundtrip("1 + 2 * 3"), "1 + 2 * 3");
+    assert_eq!(roundtrip("(1 + 2) + 3"), "1 + 2 + 3");
+    assert_eq!(roundtrip("1 + (2 + 3)"), "1 + (2 + 3)");
+    assert_eq!(
+        roundtrip("True || (False && True)"),
+        "True || False && True"
+    );
+    assert_eq!(roundtrip("(x with a = 1) ++ y"), "(x with a = 1) ++ y");
+    assert_eq!(roundtrip("f (g x) y"), "f (g x) y");
+}
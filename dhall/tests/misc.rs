@@ -3,6 +3,17 @@ use dhall::semantics::*;
 use dhall::syntax::*;
 use dhall::*;
 
+/// Parses, typechecks and normalizes `src` without resolving imports, and prints the result.
+fn normalize(src: &str) -> Result<String, Error> {
+    Ctxt::with_new(|cx| {
+        let nf = Parsed::parse_str(src)?
+            .skip_resolve(cx)?
+            .typecheck(cx)?
+            .normalize(cx);
+        Ok(nf.to_expr(cx).to_string())
+    })
+}
+
 /// Test that showcases someone using the `dhall` crate directly for a simple operation. If
 /// possible try not to break this too much. See
 /// https://github.com/Nadrieril/dhall-rust/issues/208.
@@ -48,3 +59,710 @@ fn manual_function_application() {
     // The crate uses essentially a global context, created here.
     Ctxt::with_new(run).unwrap();
 }
+
+/// Test that the types describing an import can be named and inspected from outside the crate.
+#[test]
+fn inspect_import() {
+    use dhall::imports::{FilePrefix, Import, ImportMode, ImportTarget};
+
+    let expr = Parsed::parse_str("../foo/bar.dhall as Text")
+        .unwrap()
+        .to_expr();
+    let import: &Import<Expr> = match expr.kind() {
+        ExprKind::Import(import) => import,
+        _ => panic!("not an import"),
+    };
+    assert_eq!(import.mode, ImportMode::RawText);
+    match &import.location {
+        ImportTarget::Local(prefix, path) => {
+            assert_eq!(*prefix, FilePrefix::Parent);
+            assert_eq!(path.file_path, vec!["foo", "bar.dhall"]);
+        }
+        _ => panic!("not a local import"),
+    }
+}
+
+/// Test that a let-bound value is shared between its uses instead of being substituted. Naive
+/// substitution would make this take time exponential in the number of bindings.
+#[test]
+fn repeated_let_is_shared() {
+    let n = 60;
+    let mut src = "let x0 = { a = 1 } ".to_string();
+    for i in 1..=n {
+        src += &format!("let x{} = {{ a = x{}.a + x{}.a }} ", i, i - 1, i - 1);
+    }
+    src += &format!("in x{}.a", n);
+
+    assert_eq!(normalize(&src).unwrap(), (1u64 << n).to_string());
+}
+
+#[test]
+fn diff_expressions() {
+    let parse = |s: &str| Parsed::parse_str(s).unwrap().to_expr();
+    let a = parse("{ server = { port = 80, host = \"a\" }, users = [1, 2] }");
+    let b = parse(
+        "let p = 40 + 40 in { server = { port = p, debug = True }, users = [1, 3, 4] }",
+    );
+
+    let entries = dhall::diff(&a, &b).unwrap();
+    let entries: Vec<_> = entries
+        .iter()
+        .map(|e| {
+            let show = |e: &Option<Expr>| e.as_ref().map(|e| e.to_string());
+            (e.path.as_str(), show(&e.left), show(&e.right))
+        })
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (".server.debug", None, Some("True".to_owned())),
+            (".server.host", Some("\"a\"".to_owned()), None),
+            (".users[1]", Some("2".to_owned()), Some("3".to_owned())),
+            (".users[2]", None, Some("4".to_owned())),
+        ]
+    );
+
+    // Functions are compared up to alpha-equivalence.
+    let f = parse("\\(x: Natural) -> x");
+    let g = parse("\\(y: Natural) -> y");
+    assert!(dhall::diff(&f, &g).unwrap().is_empty());
+}
+
+#[test]
+fn assert_failure_shows_both_sides() {
+    let err = normalize("assert : { x = 1 + 1 } === { x = 3 }")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("the left-hand side normalizes to: { x = 2 }"));
+    assert!(err.contains("the right-hand side normalizes to: { x = 3 }"));
+}
+
+#[test]
+fn merge_handler_errors() {
+    let typecheck = |src| normalize(src).unwrap_err().to_string();
+
+    let err = typecheck("merge { Some = \\(x: Natural) -> x } (Some 1)");
+    assert!(err.contains("handler for alternative `None` is missing"));
+    let err = typecheck("merge { B = 1 } < A | B | C : Bool | D >.B");
+    assert!(
+        err.contains("handlers for alternatives `A`, `C`, `D` are missing"),
+        "{}",
+        err
+    );
+    assert!(err.contains("this record has no handlers for `A`, `C`, `D`"));
+    let err = typecheck("merge { Foo = 1, Bar = 2 } < Foo >.Foo");
+    assert!(err.contains("handler `Bar` has no matching alternative"));
+    let err = typecheck("merge { Foo = 1, Bar = True } < Foo | Bar >.Foo");
+    assert!(err.contains("the handler for `Bar` returns: `Bool`"));
+    assert!(err.contains("but the handler for `Foo` returns: `Natural`"));
+}
+
+#[test]
+fn annotation_mismatch() {
+    let typecheck = |src| normalize(src).unwrap_err().to_string();
+
+    let err = typecheck("{ x = 1 + 1 } : { x : Bool }");
+    assert!(err.contains(
+        "expression has type `{ x : Natural }` but was annotated with `{ x : Bool }`"
+    ));
+    assert!(err.contains("^^^^^^^^^^^^^ this has type `{ x : Natural }`"));
+    assert!(err.contains("------------ help: the annotation is `{ x : Bool }`"));
+}
+
+#[test]
+fn recursive_let_binding() {
+    let typecheck = |src| normalize(src).map_err(|e| e.to_string());
+
+    let err = typecheck("let f = \\(n : Natural) -> f n in f 1").unwrap_err();
+    assert!(err.contains("recursive binding `f`"), "{}", err);
+    assert!(err.contains("Dhall doesn't support recursion"), "{}", err);
+    let err = typecheck("let x = 1 let y = [ y ] in x").unwrap_err();
+    assert!(err.contains("recursive binding `y`"), "{}", err);
+    // Other unbound variables keep the generic error.
+    let err = typecheck("let f = g in f").unwrap_err();
+    assert!(err.contains("unbound variable `g`"), "{}", err);
+    // Shadowing an outer binding is not recursion.
+    assert!(typecheck("let f = 1 let f = f + 1 in f").is_ok());
+    assert!(typecheck("let f = \\(f : Natural) -> f in f 1").is_ok());
+}
+
+#[test]
+fn partially_applied_builtins() {
+    // `List/map` is not a builtin; this is its definition from the Prelude.
+    let map = "let map = \\(a : Type) -> \\(b : Type) -> \\(f : a -> b) -> \\(xs : List a) -> \
+        List/build b (\\(list : Type) -> \\(cons : b -> list -> list) -> \
+        List/fold a xs list (\\(x : a) -> cons (f x))) in ";
+
+    // A partially applied builtin stays as it is.
+    assert_eq!(normalize("List/fold Natural").unwrap(), "List/fold Natural");
+    assert_eq!(
+        normalize("let f = List/fold Natural [1, 2] in f").unwrap(),
+        "List/fold Natural [1, 2]"
+    );
+    // It reduces once all its arguments are given.
+    assert_eq!(
+        normalize(
+            "let f = List/fold Natural [1, 2] in f Natural (\\(x : Natural) -> \\(y : Natural) -> x + y) 0"
+        ).unwrap(),
+        "3"
+    );
+    assert_eq!(
+        normalize(&format!(
+            "{} let inc = map Natural Natural (\\(x : Natural) -> x + 1) in inc [1, 2, 3]",
+            map
+        )).unwrap(),
+        "[2, 3, 4]"
+    );
+    assert_eq!(
+        normalize(&format!(
+            "{} map Natural Bool Natural/even ([] : List Natural)",
+            map
+        ))
+        .unwrap(),
+        "[] : List Bool"
+    );
+    // With an abstract list, `List/build` is reduced away and the fold is stuck.
+    assert_eq!(
+        normalize(&format!(
+            "{} \\(xs : List Natural) -> map Natural Natural (\\(x : Natural) -> x) xs",
+            map
+        )).unwrap(),
+        "λ(xs : List Natural) → List/fold Natural xs (List Natural) (λ(x : Natural) → λ(as : List Natural) → [x] # as) ([] : List Natural)"
+    );
+}
+
+#[test]
+fn text_append_folds_literals() {
+    let f = |body: &str| {
+        normalize(&format!("\\(x : Text) -> \\(y : Text) -> {}", body)).unwrap()
+    };
+
+    assert_eq!(
+        f("\"a\" ++ x ++ \"b\""),
+        "λ(x : Text) → λ(y : Text) → \"a${ x }b\""
+    );
+    assert_eq!(
+        f("(\"a\" ++ x) ++ \"b\""),
+        "λ(x : Text) → λ(y : Text) → \"a${ x }b\""
+    );
+    assert_eq!(
+        f("\"a\" ++ x ++ \"b\" ++ y ++ \"c\""),
+        "λ(x : Text) → λ(y : Text) → \"a${ x }b${ y }c\""
+    );
+    assert_eq!(
+        f("(\"a\" ++ x) ++ (\"${y}\" ++ \"b\")"),
+        "λ(x : Text) → λ(y : Text) → \"a${ x }${ y }b\""
+    );
+    // Interpolated literals are flattened too.
+    assert_eq!(
+        f("\"a${\"b${x}\"}c\""),
+        "λ(x : Text) → λ(y : Text) → \"ab${ x }c\""
+    );
+    assert_eq!(f("\"\" ++ x ++ \"\""), "λ(x : Text) → λ(y : Text) → x");
+    // Without a literal on either side, the append is stuck.
+    assert_eq!(f("x ++ y"), "λ(x : Text) → λ(y : Text) → x ++ y");
+}
+
+#[test]
+fn every_builtin_reduces() {
+    use dhall::builtins::Builtin;
+
+    // For each builtin, an application to concrete arguments and its normal form. Type
+    // constructors are already in normal form. Any builtin applied to too few or non-concrete
+    // arguments stays stuck, see `partially_applied_builtins`.
+    let cases = [
+        ("Bool", "Bool", "Bool"),
+        ("Natural", "Natural", "Natural"),
+        ("Integer", "Integer", "Integer"),
+        ("Double", "Double", "Double"),
+        ("Text", "Text", "Text"),
+        ("List", "List Bool", "List Bool"),
+        ("Optional", "Optional Bool", "Optional Bool"),
+        ("None", "None Bool", "None Bool"),
+        (
+            "Natural/build",
+            "Natural/build (\\(n : Type) -> \\(s : n -> n) -> \\(z : n) -> s (s z))",
+            "2",
+        ),
+        (
+            "Natural/fold",
+            "Natural/fold 3 Natural (\\(x : Natural) -> x * 2) 1",
+            "8",
+        ),
+        ("Natural/isZero", "Natural/isZero 0", "True"),
+        ("Natural/even", "Natural/even 3", "False"),
+        ("Natural/odd", "Natural/odd 3", "True"),
+        ("Natural/toInteger", "Natural/toInteger 3", "+3"),
+        ("Natural/show", "Natural/show 3", "\"3\""),
+        ("Natural/subtract", "Natural/subtract 1 3", "2"),
+        ("Integer/toDouble", "Integer/toDouble -2", "-2.0"),
+        ("Integer/show", "Integer/show +2", "\"+2\""),
+        ("Integer/negate", "Integer/negate +2", "-2"),
+        ("Integer/clamp", "Integer/clamp -2", "0"),
+        ("Double/show", "Double/show 1.5", "\"1.5\""),
+        (
+            "List/build",
+            "List/build Natural (\\(l : Type) -> \\(c : Natural -> l -> l) -> \\(n : l) -> c 1 n)",
+            "[1]",
+        ),
+        (
+            "List/fold",
+            "List/fold Natural [1, 2] Natural (\\(x : Natural) -> \\(y : Natural) -> x + y) 0",
+            "3",
+        ),
+        ("List/length", "List/length Natural [1, 2]", "2"),
+        ("List/head", "List/head Natural [1, 2]", "Some 1"),
+        ("List/last", "List/last Natural [1, 2]", "Some 2"),
+        ("List/indexed", "List/indexed Natural [5]", "[{ index = 0, value = 5 }]"),
+        ("List/reverse", "List/reverse Natural [1, 2]", "[2, 1]"),
+        ("Text/show", "Text/show \"a\"", "\"\\\"a\\\"\""),
+        ("Text/replace", "Text/replace \"a\" \"b\" \"aa\"", "\"bb\""),
+    ];
+
+    for b in Builtin::ALL {
+        let name = b.to_string();
+        assert_eq!(Builtin::parse(&name), Some(*b));
+        assert!(
+            cases.iter().any(|(n, _, _)| *n == name),
+            "no test case for builtin `{}`",
+            name
+        );
+    }
+    for (name, src, expected) in &cases {
+        assert_eq!(&normalize(src).unwrap(), expected, "{}", name);
+    }
+}
+
+#[test]
+fn binary_version_tag() {
+    // `["1.0.0", True]`, as encoded by old versions of the standard.
+    let data = [0x82, 0x65, b'1', b'.', b'0', b'.', b'0', 0xf5];
+    let err = Parsed::parse_binary(&data).unwrap_err().to_string();
+    assert!(err.contains("UnsupportedBinaryVersion"), "{}", err);
+    assert!(err.contains("1.0.0"), "{}", err);
+
+    // `True` without a version tag.
+    assert!(Parsed::parse_binary(&[0xf5]).is_ok());
+    // `["x", 0]` is the variable `x@0`, not a version tag.
+    assert!(Parsed::parse_binary(&[0x82, 0x61, b'x', 0x00]).is_ok());
+}
+
+#[test]
+fn free_variables() {
+    let free_vars = |s: &str| {
+        let mut vars: Vec<_> = Parsed::parse_str(s)
+            .unwrap()
+            .to_expr()
+            .free_vars()
+            .into_iter()
+            .map(|l| l.to_string())
+            .collect();
+        vars.sort();
+        vars
+    };
+
+    assert_eq!(free_vars("\\(x : Natural) -> x + y"), vec!["y"]);
+    assert_eq!(free_vars("\\(x : Bool) -> x@1"), vec!["x"]);
+    assert_eq!(
+        free_vars("\\(x : Bool) -> \\(x : Bool) -> x@1"),
+        Vec::<String>::new()
+    );
+    assert_eq!(free_vars("let a = b in a c"), vec!["b", "c"]);
+    // The bound value is not in the scope of its own binding.
+    assert_eq!(free_vars("let a = a in a"), vec!["a"]);
+    assert_eq!(free_vars("forall (t : Type) -> t -> u"), vec!["u"]);
+    assert_eq!(free_vars("\\(x : t) -> { x, y = x }"), vec!["t"]);
+
+    let is_closed =
+        |s: &str| Parsed::parse_str(s).unwrap().to_expr().is_closed();
+    assert!(is_closed("Natural/even 1"));
+    assert!(is_closed("\\(x : Natural) -> x"));
+    assert!(!is_closed("x"));
+}
+
+#[test]
+fn structural_eq() {
+    let expr = |s: &str| Parsed::parse_str(s).unwrap().to_expr();
+    let eq = |a: &str, b: &str| expr(a).structural_eq(&expr(b));
+
+    assert!(eq("1 : Natural", "1"));
+    assert!(eq("{ x = 1 : Natural }", "{ x = (1) }"));
+    assert!(eq("(([] : List Bool) : List Bool)", "[] : List Bool"));
+    assert!(eq(
+        "\\(x : Natural) -> (x : Natural) + 1",
+        "\\(x : Natural) -> x + 1"
+    ));
+    assert!(!eq("1 : Natural", "2"));
+    assert!(!eq("[] : List Bool", "[] : List Natural"));
+    assert_ne!(expr("1 : Natural"), expr("1"));
+}
+
+#[test]
+fn count_nodes_and_depth() {
+    let expr = |s: &str| Parsed::parse_str(s).unwrap().to_expr();
+
+    assert_eq!(expr("1").count_nodes(), 1);
+    assert_eq!(expr("1").depth(), 1);
+    // The application, `Natural/even` and `(1 + 2)`, which is the sum and both numbers.
+    assert_eq!(expr("Natural/even (1 + 2)").count_nodes(), 5);
+    assert_eq!(expr("Natural/even (1 + 2)").depth(), 3);
+    assert_eq!(expr("{ x = [ 1, 2 ], y = \"${\"a\"}b\" }").count_nodes(), 6);
+    assert_eq!(expr("\\(x : Natural) -> x").count_nodes(), 3);
+    // Imports count as a single node.
+    assert_eq!(expr("./foo.dhall").count_nodes(), 1);
+    assert_eq!(expr("[ [ [ 1 ] ], [ 2 ] ]").depth(), 4);
+    assert_eq!(expr("[ [ [ 1 ] ], [ 2 ] ]").count_nodes(), 6);
+}
+
+#[test]
+fn map_imports() {
+    let expr = |s: &str| Parsed::parse_str(s).unwrap().to_expr();
+    let mirror = |mut import: syntax::Import<Expr>| {
+        if let ImportTarget::Remote(url) = &mut import.location {
+            if url.authority == "old.example.com" {
+                url.authority = "mirror.example.com".to_owned();
+            }
+        }
+        import
+    };
+
+    let rewritten = expr(
+        "{ a = https://old.example.com/a.dhall, \
+         b = https://other.example.com/b.dhall, \
+         c = ./c.dhall ? https://old.example.com/c.dhall }",
+    )
+    .map_imports(mirror);
+    assert_eq!(
+        rewritten.to_string(),
+        "{ a = https://mirror.example.com/a.dhall, \
+         b = https://other.example.com/b.dhall, \
+         c = ./\"c.dhall\" ? https://mirror.example.com/c.dhall }"
+    );
+    // Imports in headers are rewritten too.
+    let rewritten = expr(
+        "https://other.example.com/a.dhall using https://old.example.com/h.dhall",
+    )
+    .map_imports(mirror);
+    assert_eq!(
+        rewritten.to_string(),
+        "https://other.example.com/a.dhall using https://mirror.example.com/h.dhall"
+    );
+}
+
+#[test]
+fn normalization_trace() {
+    let trace = |s: &str| {
+        Ctxt::with_new(|cx| {
+            let typed = Parsed::parse_str(s)
+                .unwrap()
+                .skip_resolve(cx)
+                .unwrap()
+                .typecheck(cx)
+                .unwrap();
+            cx.start_normalization_trace();
+            let normalized = typed.normalize(cx).to_expr(cx);
+            let trace: Vec<String> = cx
+                .take_normalization_trace()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+            (normalized.to_string(), trace)
+        })
+    };
+
+    let (result, steps) = trace(
+        "let T = < A : Natural | B > \
+         let handlers = { A = \\(n : Natural) -> n + 1, B = 0 } \
+         in merge handlers (T.A 41)",
+    );
+    assert_eq!(result, "42");
+    assert_eq!(
+        steps,
+        vec!["merge handlers (T.A 41)", "T.A 41", "T.A", "n + 1"]
+    );
+
+    // Variables bound outside of a step are named after their binders, even when shadowed or
+    // when the step comes from a function defined elsewhere.
+    let (result, steps) = trace(
+        "let f = \\(x : Natural) -> \\(x : Natural) -> x@1 * 10 + x in f 4 2",
+    );
+    assert_eq!(result, "42");
+    assert_eq!(steps, vec!["f 4 2", "f 4", "x@1 * 10 + x", "x@1 * 10"]);
+
+    // Branches that aren't needed are not evaluated.
+    let (_, steps) = trace("if True then 1 else 2 + 3");
+    assert_eq!(steps, vec!["if True then 1 else 2 + 3"]);
+
+    // Nothing is recorded outside of a trace.
+    Ctxt::with_new(|cx| {
+        Parsed::parse_str("1 + 1")
+            .unwrap()
+            .skip_resolve(cx)
+            .unwrap()
+            .typecheck(cx)
+            .unwrap()
+            .normalize(cx)
+            .to_expr(cx);
+        assert!(cx.take_normalization_trace().is_empty());
+    });
+}
+
+#[test]
+fn format_source() {
+    let src = r#"#!/usr/bin/env dhall
+-- Shared settings
+{- The port
+   to listen on -}
+let port : Natural = 8080
+let hosts = [ "alpha.example.com", "beta.example.com", "gamma.example.com", "delta.example.com" ]
+-- Builds the server entry
+let mkServer = \(host : Text) -> { host = host, port = port, tls = if port == 443 then True else False, name = "server" }
+in { servers = map Text { host : Text, port : Natural, tls : Bool, name : Text } mkServer hosts, debug = False }
+"#;
+    let expected = r#"#!/usr/bin/env dhall
+-- Shared settings
+{- The port
+   to listen on -}
+let port : Natural = 8080
+
+let hosts =
+      [ "alpha.example.com"
+      , "beta.example.com"
+      , "gamma.example.com"
+      , "delta.example.com"
+      ]
+
+-- Builds the server entry
+let mkServer =
+      λ(host : Text) →
+        { host = host
+        , name = "server"
+        , port = port
+        , tls = if port == 443 then True else False
+        }
+
+in  { debug = False
+    , servers =
+        map
+          Text
+          { host : Text, name : Text, port : Natural, tls : Bool }
+          mkServer
+          hosts
+    }
+"#;
+    let formatted = dhall::format(src).unwrap();
+    assert_eq!(formatted, expected);
+    // Formatting is idempotent and doesn't change the expression.
+    assert_eq!(dhall::format(&formatted).unwrap(), formatted);
+    assert_eq!(
+        Parsed::parse_str(&formatted).unwrap().to_expr(),
+        Parsed::parse_str(src).unwrap().to_expr()
+    );
+
+    assert_eq!(dhall::format("  1 +  2 ").unwrap(), "1 + 2\n");
+    assert_eq!(dhall::format("[1,2]").unwrap(), "[ 1, 2 ]\n");
+    assert!(dhall::format("{ x = ").is_err());
+}
+
+#[test]
+fn format_long_lines() {
+    let check = |src: &str, expected: &str| {
+        let formatted = dhall::format(src).unwrap();
+        assert_eq!(formatted, expected);
+        assert!(formatted.lines().all(|l| l.chars().count() <= 80));
+        assert_eq!(dhall::format(&formatted).unwrap(), formatted);
+    };
+    // A `let` in a field value gets a line per binding too.
+    check(
+        "{ config = let x = 1 in { hostname = \"example.com\", port = 8080, tls = True, name = x } }",
+        r#"{ config =
+    let x = 1
+
+    in  { hostname = "example.com", name = x, port = 8080, tls = True }
+}
+"#,
+    );
+    check(
+        r#"\(settings : { hostname : Text, port : Natural, tls : Bool, timeout : Natural }) -> \(x : Bool) -> settings.port"#,
+        r#"λ(settings
+  : { hostname : Text, port : Natural, timeout : Natural, tls : Bool }
+) →
+λ(x : Bool) →
+  settings.port
+"#,
+    );
+    check(
+        "Natural -> Text -> { aaaaaaaaaaaaaaaaaaa : Natural, bbbbbbbbbbbbbbbbbbbbb : Text } -> List Natural",
+        r#"  Natural
+→ Text
+→ { aaaaaaaaaaaaaaaaaaa : Natural, bbbbbbbbbbbbbbbbbbbbb : Text }
+→ List Natural
+"#,
+    );
+    check(
+        "Natural/fold 100000000000 Natural (\\(n : Natural) -> n + 1000000000000 + 1000000000000 + 100000000000) 0",
+        r#"Natural/fold
+  100000000000
+  Natural
+  (λ(n : Natural) → n + 1000000000000 + 1000000000000 + 100000000000)
+  0
+"#,
+    );
+    check(
+        "merge { Left = \\(n : Natural) -> n, Right = \\(b : Bool) -> if b then 1 else 0 } x : Natural",
+        r#"merge
+  { Left = λ(n : Natural) → n, Right = λ(b : Bool) → if b then 1 else 0 }
+  x
+: Natural
+"#,
+    );
+}
+
+#[test]
+fn format_comments() {
+    assert_eq!(
+        dhall::format("-- header\n{ x = 1 } -- trailing").unwrap(),
+        "-- header\n{ x = 1 }\n-- trailing\n"
+    );
+    assert_eq!(
+        dhall::format("let x = 1\n-- the result\nin x").unwrap(),
+        "let x = 1\n\n-- the result\nin  x\n"
+    );
+    assert_eq!(
+        dhall::format("{ a = {- doc -} let x = 1 in x }").unwrap(),
+        "{ a =\n    {- doc -}\n    let x = 1\n\n    in  x\n}\n"
+    );
+    // Text and imports can look like comments.
+    assert_eq!(
+        dhall::format("\"a -- b {- c\" ? ./a--b.dhall ? env:\"x--y\"").unwrap(),
+        "\"a -- b {- c\" ? ./\"a--b.dhall\" ? env:\"x--y\"\n"
+    );
+    // Comments that can't be placed are refused rather than dropped.
+    assert!(dhall::format("{ x = 1, -- one\n y = 2 }").is_err());
+    assert!(dhall::format("\"${-- one\n1}\"").is_err());
+    assert!(dhall::format("{ {- empty -} = }").is_err());
+}
+
+#[test]
+fn to_map_key_order() {
+    // Labels can only contain ASCII characters, so sorting them by byte like `BTreeMap` does
+    // matches the order of the standard.
+    assert!(Parsed::parse_str("{ `é` = True }").is_err());
+    assert_eq!(
+        normalize("toMap { b = True, `a b` = False, `B` = True, `a-b` = False, `_` = True, `0` = False }").unwrap(),
+        r#"[{ mapKey = "0", mapValue = False }, { mapKey = "B", mapValue = True }, { mapKey = "_", mapValue = True }, { mapKey = "a b", mapValue = False }, { mapKey = "a-b", mapValue = False }, { mapKey = "b", mapValue = True }]"#
+    );
+    // The order does not depend on how the record was written.
+    assert_eq!(
+        normalize(
+            "toMap ({ `z-1` = Some 1 } /\\ { b = None Natural, `B` = Some 2 })"
+        )
+        .unwrap(),
+        normalize("toMap { `B` = Some 2, b = None Natural, `z-1` = Some 1 }")
+            .unwrap(),
+    );
+    assert_eq!(
+        normalize("toMap { `z-1` = Some 1, b = None Natural, `B` = Some 2 }")
+            .unwrap(),
+        r#"[{ mapKey = "B", mapValue = Some 2 }, { mapKey = "b", mapValue = None Natural }, { mapKey = "z-1", mapValue = Some 1 }]"#
+    );
+}
+
+#[test]
+#[cfg(feature = "parse-tree-json")]
+fn parse_tree_as_json() {
+    let tree = parse_tree_json("[ 1 ]").unwrap();
+    assert_eq!(tree["rule"], "final_expression");
+    assert_eq!(tree["text"], "[ 1 ]");
+
+    // Finds the first node with the given rule.
+    fn find<'a>(
+        tree: &'a serde_json::Value,
+        rule: &str,
+    ) -> Option<&'a serde_json::Value> {
+        if tree["rule"] == rule {
+            return Some(tree);
+        }
+        tree["children"]
+            .as_array()?
+            .iter()
+            .find_map(|c| find(c, rule))
+    }
+    let lit = find(&tree, "natural_literal").unwrap();
+    assert_eq!(lit["text"], "1");
+    assert_eq!((&lit["start"], &lit["end"]), (&2.into(), &3.into()));
+
+    assert!(parse_tree_json("[ 1").is_err());
+}
+
+#[test]
+fn leading_zeros_are_rejected() {
+    for s in &["0", "+0", "-0", "0x0", "0x00", "+0x0", "10", "+100"] {
+        assert!(parse_expr(s).is_ok(), "`{}` should parse", s);
+    }
+    for s in &["00", "007", "01", "-00", "+01", "-007"] {
+        assert!(parse_expr(s).is_err(), "`{}` should not parse", s);
+    }
+    assert_eq!(parse_expr("0x00").unwrap().to_string(), "0");
+}
+
+#[test]
+fn resolve_only() {
+    let dir = std::env::temp_dir()
+        .join(format!("dhall_misc_resolve_only_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("port.dhall"), "8000 + 80").unwrap();
+
+    // Imports are inlined, but the top-level expression isn't typechecked or normalized.
+    let expr =
+        dhall::resolve_only("{ port = ./port.dhall, ok = 1 + True }", &dir)
+            .unwrap();
+    assert_eq!(expr.to_string(), "{ ok = 1 + True, port = 8080 }");
+
+    assert!(dhall::resolve_only("./missing.dhall", &dir).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn show_round_trip() {
+    use rand::Rng;
+
+    // Shows the number with the builtin, and parses the resulting text back as a literal.
+    fn round_trip(builtin: &str, literal: &str) -> String {
+        let shown = normalize(&format!("{} {}", builtin, literal)).unwrap();
+        let text = shown
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .unwrap_or_else(|| {
+                panic!("`{} {}` gave {}", builtin, literal, shown)
+            });
+        normalize(text).unwrap()
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut naturals = vec![0, 1, 9, 10, u64::MAX];
+    naturals.extend((0..200).map(|_| rng.gen::<u64>() >> rng.gen_range(0, 64)));
+    for n in naturals {
+        let literal = n.to_string();
+        assert_eq!(
+            round_trip("Natural/show", &literal),
+            normalize(&literal).unwrap(),
+            "{}",
+            literal
+        );
+    }
+
+    let mut integers = vec![0, 1, -1, i64::MIN, i64::MAX];
+    integers.extend((0..200).map(|_| rng.gen::<i64>() >> rng.gen_range(0, 64)));
+    for n in integers {
+        let literal = format!("{:+}", n);
+        assert_eq!(
+            round_trip("Integer/show", &literal),
+            normalize(&literal).unwrap(),
+            "{}",
+            literal
+        );
+    }
+}
@@ -21,18 +21,87 @@ where
     )
 }
 
+/// Whether the field is marked with `#[serde(flatten)]`.
+fn is_flattened(field: &syn::Field) -> bool {
+    field.attrs.iter().any(|attr| {
+        if !attr.path.is_ident("serde") {
+            return false;
+        }
+        match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => list.nested.iter().any(|meta| {
+                matches!(
+                    meta,
+                    syn::NestedMeta::Meta(syn::Meta::Path(path))
+                        if path.is_ident("flatten")
+                )
+            }),
+            _ => false,
+        }
+    })
+}
+
+/// Rejects the type of a flattened field when it obviously isn't a record. Other types are only
+/// checked when the static type is built.
+fn check_flattened_type(ty: &syn::Type) -> Result<(), Error> {
+    let is_record = match ty {
+        syn::Type::Path(path) => match path.path.segments.last() {
+            Some(segment) => !matches!(
+                segment.ident.to_string().as_str(),
+                "Option"
+                    | "Vec"
+                    | "VecDeque"
+                    | "HashMap"
+                    | "BTreeMap"
+                    | "HashSet"
+                    | "BTreeSet"
+                    | "String"
+                    | "bool"
+                    | "char"
+                    | "u8"
+                    | "u16"
+                    | "u32"
+                    | "u64"
+                    | "u128"
+                    | "usize"
+                    | "i8"
+                    | "i16"
+                    | "i32"
+                    | "i64"
+                    | "i128"
+                    | "isize"
+                    | "f32"
+                    | "f64"
+            ),
+            None => true,
+        },
+        syn::Type::Array(_)
+        | syn::Type::Slice(_)
+        | syn::Type::Tuple(_)
+        | syn::Type::Reference(_) => false,
+        _ => true,
+    };
+    if is_record {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ty.span(),
+            "Derive StaticType: flattened fields must have a record type",
+        ))
+    }
+}
+
 fn derive_for_struct(
     data: &syn::DataStruct,
     constraints: &mut Vec<syn::Type>,
 ) -> Result<proc_macro2::TokenStream, Error> {
-    let fields = match &data.fields {
+    let fields: Vec<_> = match &data.fields {
         syn::Fields::Named(fields) => fields
             .named
             .iter()
             .map(|f| {
                 let name = f.ident.as_ref().unwrap().to_string();
                 let ty = &f.ty;
-                (name, ty)
+                (name, ty, is_flattened(f))
             })
             .collect(),
         syn::Fields::Unnamed(fields) => fields
@@ -42,20 +111,71 @@ fn derive_for_struct(
             .map(|(i, f)| {
                 let name = format!("_{}", i + 1);
                 let ty = &f.ty;
-                (name, ty)
+                (name, ty, false)
             })
             .collect(),
         syn::Fields::Unit => vec![],
     };
-    let entries = fields.into_iter().map(|(name, ty)| {
-        constraints.push(ty.clone());
-        let ty = static_type(ty);
-        quote!( (#name.to_owned(), #ty) )
-    });
+    // Newtypes are (de)serialized like the value they wrap.
+    if let syn::Fields::Unnamed(fields) = &data.fields {
+        if fields.unnamed.len() == 1 {
+            let ty = &fields.unnamed[0].ty;
+            constraints.push(ty.clone());
+            return Ok(static_type(ty));
+        }
+    }
+    let (flattened, fields): (Vec<_>, Vec<_>) =
+        fields.into_iter().partition(|(_, _, flatten)| *flatten);
+    let entries: Vec<_> = fields
+        .into_iter()
+        .map(|(name, ty, _)| {
+            constraints.push(ty.clone());
+            let ty = static_type(ty);
+            quote!( (#name.to_owned(), #ty) )
+        })
+        .collect();
+    if flattened.is_empty() {
+        return Ok(quote! {
+            ::serde_dhall::SimpleType::Record(
+                vec![ #(#entries),* ].into_iter().collect()
+            )
+        });
+    }
+    // The fields of a flattened record are hoisted into the parent record, like serde does.
+    let flattened = flattened
+        .into_iter()
+        .map(|(_, ty, _)| {
+            check_flattened_type(ty)?;
+            constraints.push(ty.clone());
+            let name = quote!(#ty).to_string();
+            let ty = static_type(ty);
+            Ok(quote! {
+                match #ty {
+                    ::serde_dhall::SimpleType::Record(inner) => {
+                        for (k, v) in inner {
+                            if fields.insert(k.clone(), v).is_some() {
+                                panic!(
+                                    "Derive StaticType: field `{}` of flattened `{}` is already defined",
+                                    k, #name
+                                )
+                            }
+                        }
+                    }
+                    _ => panic!(
+                        "Derive StaticType: flattened field of type `{}` must have a record type",
+                        #name
+                    ),
+                }
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
     Ok(quote! {
-        ::serde_dhall::SimpleType::Record(
-            vec![ #(#entries),* ].into_iter().collect()
-        )
+        {
+            let mut fields: ::std::collections::HashMap<_, _> =
+                vec![ #(#entries),* ].into_iter().collect();
+            #(#flattened)*
+            ::serde_dhall::SimpleType::Record(fields)
+        }
     })
 }
 
@@ -0,0 +1,12 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// Parsing, resolving, typechecking and normalizing arbitrary input must
+// report errors, never panic.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        let _ = serde_dhall::from_str(s)
+            .imports(false)
+            .parse::<serde_dhall::Value>();
+    }
+});
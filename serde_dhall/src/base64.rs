@@ -0,0 +1,52 @@
+//! Reads and writes bytes as base64-encoded `Text`, for use with `#[serde(with = "...")]`.
+//!
+//! This module requires the `base64` feature.
+//!
+//! # Example
+//!
+//! ```rust
+//! # fn main() -> serde_dhall::Result<()> {
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, PartialEq, Deserialize, Serialize)]
+//! struct Key {
+//!     #[serde(with = "serde_dhall::base64")]
+//!     data: Vec<u8>,
+//! }
+//!
+//! let key: Key = serde_dhall::from_str("{ data = \"3q2+7w==\" }").parse()?;
+//! assert_eq!(key.data, vec![0xde, 0xad, 0xbe, 0xef]);
+//!
+//! let dhall = serde_dhall::serialize(&key).to_string()?;
+//! assert_eq!(dhall, "{ data = \"3q2+7w==\" }");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The field is a `Text` in Dhall, so a derived [`StaticType`](crate::StaticType) would give it
+//! the wrong type; use [`type_annotation()`](crate::Deserializer::type_annotation()) instead.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Serializes bytes as a base64-encoded `Text`.
+pub fn serialize<S>(
+    bytes: impl AsRef<[u8]>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&::base64::encode(bytes))
+}
+
+/// Deserializes a base64-encoded `Text` into bytes. Errors if the text isn't valid base64.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    ::base64::decode(&text).map_err(|e| {
+        D::Error::custom(format!("invalid base64 `{}`: {}", text, e))
+    })
+}
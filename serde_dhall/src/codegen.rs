@@ -0,0 +1,242 @@
+//! Generates Rust type definitions from a Dhall type.
+//!
+//! This goes the other way from [`StaticType`](crate::StaticType): starting from a canonical
+//! Dhall schema, it produces Rust types that the schema's values can be deserialized into. This
+//! is meant to be run from a build script, with the output written to a file and `include!`d.
+//!
+//! # Example
+//!
+//! ```rust
+//! # fn main() -> serde_dhall::Result<()> {
+//! use serde_dhall::SimpleType;
+//!
+//! let schema: SimpleType =
+//!     serde_dhall::from_str("{ name : Text, ports : List Natural }").parse()?;
+//! let code = serde_dhall::codegen::rust_types(&schema, "Config");
+//! assert_eq!(
+//!     code,
+//!     "#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+//! pub struct Config {
+//!     pub name: String,
+//!     pub ports: Vec<u64>,
+//! }
+//! "
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+
+use crate::SimpleType;
+
+const DERIVES: &str = "#[derive(Debug, Clone, PartialEq, serde::Deserialize)]";
+
+/// Returns Rust source code defining types for the Dhall type `ty`.
+///
+/// Records become structs and unions become enums, with the given name for the outermost one;
+/// nested records and unions get a name made of the name of their parent and of the field or
+/// alternative they appear in. `List` becomes `Vec`, `Optional` becomes `Option`, and `Natural`,
+/// `Integer` and `Double` become `u64`, `i64` and `f64`. If `ty` is neither a record nor a union,
+/// a type alias is generated instead, and a record or union inside it is named after the alias
+/// followed by `Item`.
+///
+/// Field and alternative names are converted to the usual Rust casing, with a
+/// `#[serde(rename = "...")]` attribute when that changes them. When several labels convert to
+/// the same name, the later ones get a number appended.
+pub fn rust_types(ty: &SimpleType, root_name: &str) -> String {
+    let mut gen = Generator {
+        out: String::new(),
+        used_names: HashSet::new(),
+    };
+    let root_name = gen.fresh_name(&type_ident(root_name));
+    match ty {
+        SimpleType::Record(_) | SimpleType::Union(_) => {
+            gen.define(ty, root_name);
+        }
+        _ => {
+            let rust_ty = gen.rust_type(ty, &(root_name.clone() + "Item"));
+            gen.out =
+                format!("pub type {} = {};\n\n", root_name, rust_ty) + &gen.out;
+        }
+    }
+    gen.out.pop();
+    gen.out
+}
+
+struct Generator {
+    out: String,
+    used_names: HashSet<String>,
+}
+
+impl Generator {
+    /// Returns `name`, or `name` followed by a number if it is already taken.
+    fn fresh_name(&mut self, name: &str) -> String {
+        unique(&mut self.used_names, name)
+    }
+
+    /// Appends the definition of the struct or enum `name`, followed by those of the types
+    /// nested in it.
+    fn define(&mut self, ty: &SimpleType, name: String) {
+        // The nested definitions are generated separately so that they come after this one.
+        let mut nested = Generator {
+            out: String::new(),
+            used_names: std::mem::take(&mut self.used_names),
+        };
+        // Different labels can map to the same field or variant name.
+        let mut idents = HashSet::new();
+        let mut def = format!("{}\n", DERIVES);
+        match ty {
+            SimpleType::Record(_) => {
+                def.push_str(&format!("pub struct {} {{\n", name));
+                for (field, field_ty) in ty.record_fields().unwrap() {
+                    let ident = unique(&mut idents, &field_ident(&field));
+                    push_rename(&mut def, &ident, &field);
+                    let rust_ty = nested.rust_type(
+                        &field_ty,
+                        &(name.clone() + &type_ident(&field)),
+                    );
+                    def.push_str(&format!("    pub {}: {},\n", ident, rust_ty));
+                }
+            }
+            SimpleType::Union(_) => {
+                def.push_str(&format!("pub enum {} {{\n", name));
+                for (variant, payload) in ty.union_variants().unwrap() {
+                    let ident = unique(&mut idents, &type_ident(&variant));
+                    push_rename(&mut def, &ident, &variant);
+                    match payload {
+                        None => def.push_str(&format!("    {},\n", ident)),
+                        Some(payload) => {
+                            let rust_ty = nested
+                                .rust_type(&payload, &(name.clone() + &ident));
+                            def.push_str(&format!(
+                                "    {}({}),\n",
+                                ident, rust_ty
+                            ));
+                        }
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+        def.push_str("}\n\n");
+        self.out.push_str(&def);
+        self.out.push_str(&nested.out);
+        self.used_names = nested.used_names;
+    }
+
+    /// Returns the Rust type for `ty`, defining a new type named after `name` if it is a record
+    /// or union.
+    fn rust_type(&mut self, ty: &SimpleType, name: &str) -> String {
+        match ty {
+            SimpleType::Bool => "bool".to_owned(),
+            SimpleType::Natural => "u64".to_owned(),
+            SimpleType::Integer => "i64".to_owned(),
+            SimpleType::Double => "f64".to_owned(),
+            SimpleType::Text => "String".to_owned(),
+            SimpleType::Optional(t) => {
+                format!("Option<{}>", self.rust_type(t, name))
+            }
+            SimpleType::List(t) => format!("Vec<{}>", self.rust_type(t, name)),
+            SimpleType::Record(_) | SimpleType::Union(_) => {
+                let name = self.fresh_name(name);
+                self.define(ty, name.clone());
+                name
+            }
+        }
+    }
+}
+
+/// Returns `ident`, or `ident` followed by a number if it is already in `used`, and adds it to
+/// `used`.
+fn unique(used: &mut HashSet<String>, ident: &str) -> String {
+    let mut candidate = ident.to_owned();
+    let mut i = 2;
+    while used.contains(&candidate) {
+        // A keyword followed by a number is no longer a keyword.
+        candidate = format!("{}{}", ident.trim_start_matches("r#"), i);
+        i += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+fn push_rename(def: &mut String, ident: &str, label: &str) {
+    if ident.trim_start_matches("r#") != label {
+        def.push_str(&format!("    #[serde(rename = {:?})]\n", label));
+    }
+}
+
+/// Splits a label into words, at non-alphanumeric characters and before inner capitals.
+fn words(label: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in label.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower {
+            words.push(std::mem::take(&mut current));
+        }
+        prev_lower = c.is_lowercase() || c.is_numeric();
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Converts a label to an `UpperCamelCase` identifier.
+fn type_ident(label: &str) -> String {
+    let ident: String = words(label)
+        .iter()
+        .map(|w| {
+            let mut chars = w.chars();
+            let first = chars.next().unwrap().to_uppercase();
+            first
+                .chain(chars.flat_map(char::to_lowercase))
+                .collect::<String>()
+        })
+        .collect();
+    fix_ident(ident)
+}
+
+/// Converts a label to a `snake_case` identifier.
+fn field_ident(label: &str) -> String {
+    let ident = words(label)
+        .iter()
+        .map(|w| w.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_");
+    fix_ident(ident)
+}
+
+/// Makes sure `ident` is a valid identifier.
+fn fix_ident(ident: String) -> String {
+    const KEYWORDS: &[&str] = &[
+        "abstract", "as", "async", "await", "become", "box", "break", "const",
+        "continue", "dyn", "else", "enum", "extern", "false", "final", "fn",
+        "for", "if", "impl", "in", "let", "loop", "macro", "match", "mod",
+        "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+        "struct", "trait", "true", "try", "type", "typeof", "unsafe",
+        "unsized", "use", "virtual", "where", "while", "yield",
+    ];
+    if ident.is_empty() {
+        "unnamed".to_owned()
+    } else if ident.starts_with(|c: char| c.is_numeric()) {
+        format!("_{}", ident)
+    } else if KEYWORDS.contains(&ident.as_str()) {
+        format!("r#{}", ident)
+    } else if ["self", "Self", "super", "crate"].contains(&ident.as_str()) {
+        // These can't be raw identifiers.
+        format!("{}_", ident)
+    } else {
+        ident
+    }
+}
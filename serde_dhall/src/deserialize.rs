@@ -1,16 +1,14 @@
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 
-use serde::de::value::{
-    MapAccessDeserializer, MapDeserializer, SeqDeserializer,
-};
-use serde::de::VariantAccess as _;
+use serde::de::value::{MapAccessDeserializer, MapDeserializer};
+use serde::de::{DeserializeSeed, IntoDeserializer, VariantAccess as _};
 
 use dhall::syntax::NumKind;
 
-use crate::value::SimpleValue;
-use crate::{Error, ErrorKind, Value};
+use crate::value::{SimpleValue, ValueKind};
+use crate::{Error, ErrorKind, SimpleType, Value};
 
 pub trait Sealed {}
 
@@ -20,6 +18,10 @@ pub trait Sealed {}
 /// In fact, this trait cannot be implemented manually. To implement it for your type,
 /// use serde's derive mechanism.
 ///
+/// The Dhall expression is evaluated before being deserialized, so the resulting data can't
+/// borrow from the source text: fields like `&str` or `#[serde(borrow)] Cow<'a, str>` aren't
+/// supported. Use `String` or `Cow<'static, str>` instead.
+///
 /// # Example
 ///
 /// ```rust
@@ -42,7 +44,29 @@ pub trait Sealed {}
 /// [serde]: https://serde.rs
 pub trait FromDhall: Sealed + Sized {
     #[doc(hidden)]
-    fn from_dhall(v: &Value) -> crate::Result<Self>;
+    fn from_dhall(v: &Value) -> crate::Result<Self> {
+        Self::from_dhall_with_options(v, DeserializeOptions::default())
+    }
+    #[doc(hidden)]
+    fn from_dhall_with_options(
+        v: &Value,
+        opts: DeserializeOptions,
+    ) -> crate::Result<Self>;
+}
+
+/// Options that control how a Dhall value is converted to a Rust value. They are set with the
+/// methods of [`Deserializer`](crate::Deserializer).
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeserializeOptions {
+    /// Match record fields to struct fields ignoring case.
+    pub(crate) field_case_insensitive: bool,
+    /// Accept a `Natural` `0` or `1` where a `bool` is expected.
+    pub(crate) naturals_as_bools: bool,
+    /// Accept a value that isn't an `Optional` where an `Option` is expected.
+    pub(crate) coerce_optional: bool,
+    /// Accept a `Natural` or `Integer` where a float is expected.
+    pub(crate) numbers_as_doubles: bool,
 }
 
 impl<T> Sealed for T where T: serde::de::DeserializeOwned {}
@@ -88,25 +112,43 @@ pub fn from_simple_value<T>(v: SimpleValue) -> crate::Result<T>
 where
     T: serde::de::DeserializeOwned,
 {
-    T::deserialize(Deserializer(Cow::Owned(v)))
+    T::deserialize(Deserializer(Cow::Owned(v), DeserializeOptions::default()))
 }
 
 impl<T> FromDhall for T
 where
     T: serde::de::DeserializeOwned,
 {
-    fn from_dhall(v: &Value) -> crate::Result<Self> {
-        let sval = v.to_simple_value().ok_or_else(|| {
-            Error(ErrorKind::Deserialize(format!(
-                "this cannot be deserialized into the serde data model: {}",
-                v
-            )))
-        })?;
-        from_simple_value(sval)
+    fn from_dhall_with_options(
+        v: &Value,
+        opts: DeserializeOptions,
+    ) -> crate::Result<Self> {
+        T::deserialize(ValueDeserializer(v, opts))
     }
 }
 
-struct Deserializer<'a>(Cow<'a, SimpleValue>);
+/// Drives `seed` over the given value, like `FromDhall::from_dhall_with_options` does for a
+/// `Deserialize` type.
+pub(crate) fn from_dhall_seed<S, V>(
+    v: &Value,
+    opts: DeserializeOptions,
+    seed: S,
+) -> crate::Result<V>
+where
+    S: for<'de> DeserializeSeed<'de, Value = V>,
+{
+    seed.deserialize(ValueDeserializer(v, opts))
+}
+
+/// The name `SimpleType` passes to `deserialize_newtype_struct`, so that `ValueDeserializer` can
+/// tell it apart from other types and hand it a Dhall type. Other formats ignore it.
+const SIMPLE_TYPE_TOKEN: &str = "$serde_dhall::private::SimpleType";
+
+/// Deserializes a `Value`. Values are read by `Deserializer`; types can only be read into a
+/// `SimpleType`, and records of types into records of those.
+struct ValueDeserializer<'a>(&'a Value, DeserializeOptions);
+
+struct Deserializer<'a>(Cow<'a, SimpleValue>, DeserializeOptions);
 
 impl<'de: 'a, 'a> serde::de::IntoDeserializer<'de, Error> for Deserializer<'a> {
     type Deserializer = Deserializer<'a>;
@@ -115,6 +157,48 @@ impl<'de: 'a, 'a> serde::de::IntoDeserializer<'de, Error> for Deserializer<'a> {
     }
 }
 
+impl<'a> Deserializer<'a> {
+    /// Errors if the value is a `Natural` or `Integer` outside of `min..=max`, naming the
+    /// literal instead of leaving serde to report a generic invalid value.
+    fn check_int_range(
+        &self,
+        min: i128,
+        max: i128,
+        target: &'static str,
+    ) -> crate::Result<()> {
+        let (n, value) = match self.0.as_ref() {
+            SimpleValue::Num(NumKind::Natural(n)) => {
+                (i128::from(*n), n.to_string())
+            }
+            SimpleValue::Num(NumKind::Integer(n)) => {
+                (i128::from(*n), format!("{:+}", n))
+            }
+            _ => return Ok(()),
+        };
+        if n < min || n > max {
+            return Err(Error(ErrorKind::IntegerOutOfRange { value, target }));
+        }
+        Ok(())
+    }
+
+    /// `Natural` and `Integer` are distinct from `Double`, so they are only read as floats if
+    /// `numbers_as_doubles` is set.
+    fn check_double(&self) -> crate::Result<()> {
+        let found = match self.0.as_ref() {
+            SimpleValue::Num(NumKind::Natural(_)) => "Natural",
+            SimpleValue::Num(NumKind::Integer(_)) => "Integer",
+            _ => return Ok(()),
+        };
+        if !self.1.numbers_as_doubles {
+            return Err(Error(ErrorKind::TypeMismatch {
+                expected: "Double",
+                found,
+            }));
+        }
+        Ok(())
+    }
+}
+
 impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
     type Error = Error;
 
@@ -125,25 +209,53 @@ impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
         use NumKind::*;
         use SimpleValue::*;
 
-        let val = |x| Deserializer(Cow::Borrowed(x));
-        match self.0.as_ref() {
+        let opts = self.1;
+        let owned = |x| Deserializer(Cow::Owned(x), opts);
+        let sval = match self.0 {
+            // Move out of owned values so that strings are not copied again.
+            Cow::Owned(Text(x)) => return visitor.visit_string(x),
+            Cow::Owned(List(xs)) => {
+                return visitor
+                    .visit_seq(Elements::new(xs.into_iter().map(owned)))
+            }
+            Cow::Owned(Optional(Some(x))) => {
+                return visitor.visit_some(owned(*x))
+            }
+            Cow::Owned(Record(m)) => {
+                return visitor.visit_map(Fields::new(
+                    m.into_iter()
+                        .map(|(k, v)| (Key::Field(Cow::Owned(k)), owned(v))),
+                ))
+            }
+            Cow::Owned(Union(field_name, Some(x))) => {
+                return visitor.visit_enum(MapAccessDeserializer::new(
+                    Fields::new(std::iter::once((
+                        Key::Field(Cow::Owned(field_name)),
+                        owned(*x),
+                    ))),
+                ))
+            }
+            sval => sval,
+        };
+        let val = |x| Deserializer(Cow::Borrowed(x), opts);
+        match sval.as_ref() {
             Num(Bool(x)) => visitor.visit_bool(*x),
             Num(Natural(x)) => visitor.visit_u64(*x),
             Num(Integer(x)) => visitor.visit_i64(*x),
             Num(Double(x)) => visitor.visit_f64((*x).into()),
             Text(x) => visitor.visit_str(x),
-            List(xs) => {
-                visitor.visit_seq(SeqDeserializer::new(xs.iter().map(val)))
-            }
+            List(xs) => visitor.visit_seq(Elements::new(xs.iter().map(val))),
             Optional(None) => visitor.visit_none(),
             Optional(Some(x)) => visitor.visit_some(val(x)),
-            Record(m) => visitor.visit_map(MapDeserializer::new(
-                m.iter().map(|(k, v)| (k.as_str(), val(v))),
+            Record(m) => visitor.visit_map(Fields::new(
+                m.iter()
+                    .map(|(k, v)| (Key::Field(Cow::Borrowed(k)), val(v))),
             )),
             Union(field_name, Some(x)) => visitor.visit_enum(
-                MapAccessDeserializer::new(MapDeserializer::new(
-                    Some((field_name.as_str(), val(x))).into_iter(),
-                )),
+                MapAccessDeserializer::new(Fields::new(std::iter::once((
+                    Key::Field(Cow::Borrowed(field_name)),
+                    val(x),
+                )))),
             ),
             Union(field_name, None) => visitor.visit_enum(
                 MapAccessDeserializer::new(MapDeserializer::new(
@@ -161,11 +273,45 @@ impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
     where
         V: serde::de::Visitor<'de>,
     {
-        let val = |x| Deserializer(Cow::Borrowed(x));
+        let opts = self.1;
+        let val = |x| Deserializer(Cow::Borrowed(x), opts);
         match self.0.as_ref() {
             // Blindly takes keys in sorted order.
-            SimpleValue::Record(m) => visitor
-                .visit_seq(SeqDeserializer::new(m.iter().map(|(_, v)| val(v)))),
+            SimpleValue::Record(m) => {
+                visitor.visit_seq(Elements::new(m.iter().map(|(_, v)| val(v))))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.0.as_ref() {
+            SimpleValue::Num(NumKind::Natural(n))
+                if self.1.naturals_as_bools =>
+            {
+                match n {
+                    0 => visitor.visit_bool(false),
+                    1 => visitor.visit_bool(true),
+                    n => Err(Error(ErrorKind::Deserialize(format!(
+                        "expected 0 or 1 for a boolean, found {}",
+                        n
+                    )))),
+                }
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.0.as_ref() {
+            SimpleValue::Optional(_) => self.deserialize_any(visitor),
+            _ if self.1.coerce_optional => visitor.visit_some(self),
             _ => self.deserialize_any(visitor),
         }
     }
@@ -180,11 +326,463 @@ impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
         }
     }
 
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let opts = self.1;
+        let val = |x| Deserializer(Cow::Borrowed(x), opts);
+        match self.0.as_ref() {
+            SimpleValue::Record(m) if opts.field_case_insensitive => {
+                let m = match_fields_ignoring_case(m, fields)?;
+                visitor.visit_map(Fields::new(
+                    m.into_iter()
+                        .map(|(k, v)| (Key::Field(Cow::Borrowed(k)), val(v))),
+                ))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.check_int_range(i8::MIN.into(), i8::MAX.into(), "i8")?;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.check_int_range(i16::MIN.into(), i16::MAX.into(), "i16")?;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.check_int_range(i32::MIN.into(), i32::MAX.into(), "i32")?;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.check_int_range(i64::MIN.into(), i64::MAX.into(), "i64")?;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.check_int_range(0, u8::MAX.into(), "u8")?;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.check_int_range(0, u16::MAX.into(), "u16")?;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.check_int_range(0, u32::MAX.into(), "u32")?;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.check_int_range(0, u64::MAX.into(), "u64")?;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // `Natural`s are 64-bit, so they always fit.
+        self.check_int_range(0, i128::MAX, "u128")?;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.check_double()?;
+        match self.0.as_ref() {
+            // `NaN` and infinities convert as expected, but a finite value must not silently
+            // become infinite.
+            SimpleValue::Num(NumKind::Double(x))
+                if f64::from(*x).is_finite()
+                    && f64::from(*x).abs() > f64::from(f32::MAX) =>
+            {
+                Err(Error(ErrorKind::Deserialize(format!(
+                    "double {:e} is out of range for f32",
+                    f64::from(*x)
+                ))))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.check_double()?;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.0 {
+            // The alternative of a union without payload is read as its name.
+            Cow::Owned(SimpleValue::Union(name, None)) => {
+                visitor.visit_string(name)
+            }
+            Cow::Borrowed(SimpleValue::Union(name, None)) => {
+                visitor.visit_str(name)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.0.as_ref() {
+            SimpleValue::Text(text) => {
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => visitor.visit_char(c),
+                    _ => Err(Error(ErrorKind::ExpectedSingleChar {
+                        text: text.clone(),
+                    })),
+                }
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if name == SIMPLE_TYPE_TOKEN {
+            return Err(Error(ErrorKind::Deserialize(
+                "expected a type, found a value".to_owned(),
+            )));
+        }
+        // A newtype is read like the value it wraps.
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let opts = self.1;
+        let val = |x| Deserializer(Cow::Borrowed(x), opts);
+        // Lists of `{ mapKey, mapValue }` with text keys are already records. With other keys,
+        // they are only read as maps when a map is expected.
+        if let SimpleValue::List(xs) = self.0.as_ref() {
+            let entries = xs
+                .iter()
+                .enumerate()
+                .map(|(i, x)| match x {
+                    SimpleValue::Record(kvs) if kvs.len() == 2 => Some((
+                        Key::Entry(i, val(kvs.get("mapKey")?)),
+                        val(kvs.get("mapValue")?),
+                    )),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+            if let Some(entries) = entries {
+                return visitor.visit_map(Fields::new(entries.into_iter()));
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
     serde::forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf option unit_struct newtype_struct seq
-        tuple_struct map struct enum identifier ignored_any
+        i128 bytes byte_buf seq
+        tuple_struct enum identifier ignored_any
+    }
+}
+
+/// Implements a deserializer method by passing values on to `Deserializer`, and types to
+/// `deserialize_any`.
+macro_rules! forward_to_simple_value {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {$(
+        fn $method<V>(self, $($arg: $ty,)* visitor: V) -> crate::Result<V::Value>
+        where
+            V: serde::de::Visitor<'de>,
+        {
+            match self.0.kind() {
+                ValueKind::Val(x, _) => {
+                    Deserializer(Cow::Borrowed(x), self.1).$method($($arg,)* visitor)
+                }
+                _ => self.deserialize_any(visitor),
+            }
+        }
+    )*};
+}
+
+impl<'de: 'a, 'a> serde::Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let opts = self.1;
+        match self.0.kind() {
+            ValueKind::Val(x, _) => {
+                Deserializer(Cow::Borrowed(x), opts).deserialize_any(visitor)
+            }
+            ValueKind::Ty(_) => Err(Error(ErrorKind::Deserialize(format!(
+                "expected a value, found the type {}",
+                self.0
+            )))),
+            ValueKind::TyRecord(kvs) => {
+                visitor.visit_map(Fields::new(kvs.iter().map(|(k, v)| {
+                    (Key::Field(Cow::Borrowed(k)), ValueDeserializer(v, opts))
+                })))
+            }
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.0.kind() {
+            ValueKind::Val(x, _) => Deserializer(Cow::Borrowed(x), self.1)
+                .deserialize_newtype_struct(name, visitor),
+            // `SimpleType` reads itself from the shape it serializes to.
+            ValueKind::Ty(ty) if name == SIMPLE_TYPE_TOKEN => visitor
+                .visit_newtype_struct(Deserializer(
+                    Cow::Owned(ty.to_simple_value()?),
+                    self.1,
+                )),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_simple_value! {
+        deserialize_bool() deserialize_i8() deserialize_i16() deserialize_i32()
+        deserialize_i64() deserialize_i128() deserialize_u8() deserialize_u16()
+        deserialize_u32() deserialize_u64() deserialize_u128() deserialize_f32()
+        deserialize_f64() deserialize_char() deserialize_str() deserialize_string()
+        deserialize_bytes() deserialize_byte_buf() deserialize_option()
+        deserialize_unit() deserialize_unit_struct(name: &'static str)
+        deserialize_seq() deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_identifier() deserialize_ignored_any()
+    }
+}
+
+/// Where a value is inside its parent, to report the path to a value that fails to deserialize.
+enum PathSegment<'a> {
+    Field(Cow<'a, str>),
+    Index(usize),
+}
+
+impl fmt::Display for PathSegment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathSegment::Field(name) => write!(f, "{}", name),
+            PathSegment::Index(i) => write!(f, "[{}]", i),
+        }
+    }
+}
+
+fn exact_size_hint(iter: &impl Iterator) -> Option<usize> {
+    match iter.size_hint() {
+        (lower, Some(upper)) if lower == upper => Some(upper),
+        _ => None,
+    }
+}
+
+/// Like serde's `SeqDeserializer`, but adds the index of the element to its errors.
+struct Elements<I>(std::iter::Enumerate<I>);
+
+impl<I: Iterator> Elements<I> {
+    fn new(iter: I) -> Self {
+        Elements(iter.enumerate())
+    }
+}
+
+impl<'de: 'a, 'a, I> serde::de::SeqAccess<'de> for Elements<I>
+where
+    I: Iterator<Item = Deserializer<'a>>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(
+        &mut self,
+        seed: T,
+    ) -> crate::Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.0.next() {
+            Some((i, x)) => seed
+                .deserialize(x)
+                .map(Some)
+                .map_err(|e| e.prefix_path(&PathSegment::Index(i).to_string())),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        exact_size_hint(&self.0)
+    }
+}
+
+/// The key of an entry read by `Fields`: either a record field, or the `mapKey` of the `i`th
+/// element of a list of `{ mapKey, mapValue }`.
+enum Key<'a> {
+    Field(Cow<'a, str>),
+    Entry(usize, Deserializer<'a>),
+}
+
+/// Like serde's `MapDeserializer`, but adds the key of the entry to its errors.
+struct Fields<'a, D, I> {
+    iter: I,
+    value: Option<(PathSegment<'a>, D)>,
+}
+
+impl<'a, D, I> Fields<'a, D, I> {
+    fn new(iter: I) -> Self {
+        Fields { iter, value: None }
+    }
+}
+
+impl<'de: 'a, 'a, D, I> serde::de::MapAccess<'de> for Fields<'a, D, I>
+where
+    D: serde::Deserializer<'de, Error = Error>,
+    I: Iterator<Item = (Key<'a>, D)>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> crate::Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let (key, val) = match self.iter.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let (key, segment) = match key {
+            Key::Field(name) => {
+                let de: serde::de::value::StrDeserializer<Error> =
+                    name.as_ref().into_deserializer();
+                (seed.deserialize(de)?, PathSegment::Field(name))
+            }
+            Key::Entry(i, de) => {
+                let segment = PathSegment::Index(i);
+                let key = seed
+                    .deserialize(de)
+                    .map_err(|e| e.prefix_path(&segment.to_string()))?;
+                (key, segment)
+            }
+        };
+        self.value = Some((segment, val));
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> crate::Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (segment, val) = self
+            .value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(val)
+            .map_err(|e| e.prefix_path(&segment.to_string()))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        exact_size_hint(&self.iter)
+    }
+}
+
+/// Renames the fields of the record to the struct fields that are equal to them ignoring case.
+/// Errors if two fields of the record are equal ignoring case.
+fn match_fields_ignoring_case<'a>(
+    m: &'a BTreeMap<String, SimpleValue>,
+    fields: &'static [&'static str],
+) -> crate::Result<Vec<(&'a str, &'a SimpleValue)>> {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    let mut matched = Vec::new();
+    for (k, v) in m {
+        let lowercase = k.to_lowercase();
+        if let Some(other) = seen.insert(lowercase.clone(), k) {
+            return Err(Error(ErrorKind::Deserialize(format!(
+                "fields `{}` and `{}` only differ in case",
+                other, k
+            ))));
+        }
+        let field = fields
+            .iter()
+            .find(|f| f.to_lowercase() == lowercase)
+            .copied()
+            .unwrap_or(k);
+        matched.push((field, v));
     }
+    Ok(matched)
 }
 
 struct SimpleValueVisitor;
@@ -276,3 +874,72 @@ impl<'de> serde::de::Deserialize<'de> for SimpleValue {
         deserializer.deserialize_any(SimpleValueVisitor)
     }
 }
+
+/// The shape `SimpleType` serializes to.
+#[derive(serde::Deserialize)]
+#[serde(rename = "SimpleType")]
+enum SimpleTypeRepr {
+    Bool,
+    Natural,
+    Integer,
+    Double,
+    Text,
+    Optional(Box<SimpleTypeRepr>),
+    List(Box<SimpleTypeRepr>),
+    Record(HashMap<String, SimpleTypeRepr>),
+    Union(HashMap<String, Option<SimpleTypeRepr>>),
+}
+
+impl From<SimpleTypeRepr> for SimpleType {
+    fn from(repr: SimpleTypeRepr) -> SimpleType {
+        use SimpleTypeRepr::*;
+        match repr {
+            Bool => SimpleType::Bool,
+            Natural => SimpleType::Natural,
+            Integer => SimpleType::Integer,
+            Double => SimpleType::Double,
+            Text => SimpleType::Text,
+            Optional(t) => SimpleType::Optional(Box::new((*t).into())),
+            List(t) => SimpleType::List(Box::new((*t).into())),
+            Record(kts) => SimpleType::Record(
+                kts.into_iter().map(|(k, t)| (k, t.into())).collect(),
+            ),
+            Union(kts) => SimpleType::Union(
+                kts.into_iter()
+                    .map(|(k, t)| (k, t.map(SimpleType::from)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+struct SimpleTypeVisitor;
+
+impl<'de> serde::de::Visitor<'de> for SimpleTypeVisitor {
+    type Value = SimpleType;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a simple Dhall type")
+    }
+
+    fn visit_newtype_struct<D>(self, d: D) -> Result<SimpleType, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::Deserialize;
+        SimpleTypeRepr::deserialize(d).map(SimpleType::from)
+    }
+}
+
+/// A Dhall type is only read into a `SimpleType`, and a `SimpleType` only from a Dhall type: the
+/// `serde_dhall` deserializer recognizes the newtype name. Other formats read the shape given by
+/// the `Serialize` impl.
+impl<'de> serde::de::Deserialize<'de> for SimpleType {
+    fn deserialize<D>(deserializer: D) -> Result<SimpleType, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_newtype_struct(SIMPLE_TYPE_TOKEN, SimpleTypeVisitor)
+    }
+}
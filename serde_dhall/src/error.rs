@@ -1,5 +1,7 @@
 use dhall::error::Error as DhallError;
 
+use crate::SimpleType;
+
 /// Alias for a `Result` with the error type `serde_dhall::Error`.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -12,6 +14,27 @@ pub(crate) enum ErrorKind {
     Dhall(DhallError),
     Deserialize(String),
     Serialize(String),
+    Multiple(Vec<Error>),
+    TypeNotExact {
+        expected: SimpleType,
+        inferred: String,
+    },
+    IntegerOutOfRange {
+        value: String,
+        target: &'static str,
+    },
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    ExpectedSingleChar {
+        text: String,
+    },
+    /// An error that occurred while deserializing the value at `path`.
+    AtPath {
+        path: String,
+        error: Box<Error>,
+    },
 }
 
 impl From<ErrorKind> for Error {
@@ -20,12 +43,161 @@ impl From<ErrorKind> for Error {
     }
 }
 
+impl Error {
+    /// Returns the individual errors that make up this error.
+    ///
+    /// When [`collect_errors()`] is enabled, a single call to [`parse()`] can report several
+    /// independent errors at once. Any other error is returned on its own.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use std::collections::HashMap;
+    /// use serde_dhall::{from_str, SimpleType};
+    ///
+    /// let ty = from_str("{ x: Natural, y: Natural, z: Natural }").parse::<SimpleType>()?;
+    /// let err = from_str("{ x = True, y = 1, z = \"foo\" }")
+    ///     .type_annotation(&ty)
+    ///     .collect_errors(true)
+    ///     .parse::<HashMap<String, u64>>()
+    ///     .unwrap_err();
+    /// assert_eq!(err.errors().count(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`collect_errors()`]: crate::Deserializer::collect_errors()
+    /// [`parse()`]: crate::Deserializer::parse()
+    pub fn errors(&self) -> impl Iterator<Item = &Error> {
+        match &self.0 {
+            ErrorKind::Multiple(errs) => errs.iter().collect::<Vec<_>>(),
+            _ => vec![self],
+        }
+        .into_iter()
+    }
+
+    /// Returns the path to the value that failed to deserialize, like `server.listeners[2].port`,
+    /// if the error didn't happen at the top level.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Listener {
+    ///     port: u16,
+    /// }
+    /// #[derive(Debug, Deserialize)]
+    /// struct Server {
+    ///     listeners: Vec<Listener>,
+    /// }
+    ///
+    /// let err = serde_dhall::from_str("{ listeners = [ { port = 80 }, { port = 100000 } ] }")
+    ///     .parse::<Server>()
+    ///     .unwrap_err();
+    /// assert_eq!(err.path(), Some("listeners[1].port"));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "integer 100000 is out of range for u16 at `listeners[1].port`"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn path(&self) -> Option<&str> {
+        match &self.0 {
+            ErrorKind::AtPath { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Records that this error happened inside the field or list element `segment`, which is
+    /// either a field name or an index like `[2]`.
+    pub(crate) fn prefix_path(self, segment: &str) -> Error {
+        let join = |rest: &str| {
+            if rest.starts_with('[') {
+                format!("{}{}", segment, rest)
+            } else {
+                format!("{}.{}", segment, rest)
+            }
+        };
+        match self.0 {
+            ErrorKind::AtPath { path, error } => Error(ErrorKind::AtPath {
+                path: join(&path),
+                error,
+            }),
+            kind => Error(ErrorKind::AtPath {
+                path: segment.to_owned(),
+                error: Box::new(Error(kind)),
+            }),
+        }
+    }
+}
+
+impl From<DhallError> for Error {
+    fn from(err: DhallError) -> Error {
+        use dhall::error::{ErrorKind as DhallErrorKind, TypeMessage};
+        let is_multiple = match err.kind() {
+            DhallErrorKind::Typecheck(err) => {
+                matches!(err.message(), TypeMessage::Multiple(_))
+            }
+            _ => false,
+        };
+        if !is_multiple {
+            return Error(ErrorKind::Dhall(err));
+        }
+        match err.into_kind() {
+            DhallErrorKind::Typecheck(err) => Error(ErrorKind::Multiple(
+                err.into_errors()
+                    .into_iter()
+                    .map(|err| Error(ErrorKind::Dhall(err.into())))
+                    .collect(),
+            )),
+            _ => unreachable!(),
+        }
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match &self.0 {
             ErrorKind::Dhall(err) => write!(f, "{}", err),
             ErrorKind::Deserialize(err) => write!(f, "{}", err),
             ErrorKind::Serialize(err) => write!(f, "{}", err),
+            ErrorKind::TypeNotExact { expected, inferred } => write!(
+                f,
+                "expected a value of exactly type {}, found a value of type {}",
+                expected, inferred
+            ),
+            ErrorKind::IntegerOutOfRange { value, target } => {
+                write!(f, "integer {} is out of range for {}", value, target)
+            }
+            ErrorKind::TypeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "expected a value of type {}, found a value of type {}",
+                    expected, found
+                )
+            }
+            ErrorKind::ExpectedSingleChar { text } => write!(
+                f,
+                "expected a text of exactly one character, found {:?}",
+                text
+            ),
+            ErrorKind::AtPath { path, error } => {
+                write!(f, "{} at `{}`", error, path)
+            }
+            ErrorKind::Multiple(errs) => {
+                for (i, err) in errs.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", err)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -258,6 +258,9 @@ mod test_readme {
     doc_comment::doctest!("../../README.md");
 }
 
+#[cfg(feature = "base64")]
+pub mod base64;
+pub mod codegen;
 mod deserialize;
 mod error;
 mod options;
@@ -269,11 +272,19 @@ mod value;
 #[doc(hidden)]
 pub use dhall_proc_macros::StaticType;
 
+/// The types describing imports, as passed to [`Deserializer::on_import()`].
+pub use dhall::imports;
+
 pub use deserialize::{from_simple_value, FromDhall};
 pub(crate) use error::ErrorKind;
 pub use error::{Error, Result};
-pub use options::de::{from_binary_file, from_file, from_str, Deserializer};
+pub use options::de::{
+    from_binary_file, from_file, from_files, from_str, validate, Deserializer,
+    Timing,
+};
 pub use options::ser::{serialize, Serializer};
 pub use serialize::ToDhall;
 pub use static_type::StaticType;
-pub use value::{NumKind, SimpleType, SimpleValue, Value};
+pub use value::{
+    deep_merge, LazyValue, NumKind, SimpleType, SimpleValue, Value,
+};
@@ -1,20 +1,97 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use dhall::{Ctxt, Parsed};
+use dhall::{Ctxt, ImportHook, Parsed, ResolveOptions};
 
+use crate::deserialize::DeserializeOptions;
+use crate::imports::{ImportLocation, ImportMode};
 use crate::options::{HasAnnot, ManualAnnot, NoAnnot, StaticAnnot, TypeAnnot};
 use crate::SimpleType;
-use crate::{Error, ErrorKind, FromDhall, Result, Value};
+use crate::{Error, ErrorKind, FromDhall, LazyValue, Result, ToDhall, Value};
 
 #[derive(Debug, Clone)]
 enum Source<'a> {
     Str(&'a str),
     File(PathBuf),
     BinaryFile(PathBuf),
+    /// Merged with `//`, the last file taking precedence.
+    Files(Vec<PathBuf>),
     // Url(&'a str),
 }
 
+/// The time spent in each stage of reading a Dhall value, as returned by
+/// [`Deserializer::parse_with_timing()`].
+///
+/// The time spent fetching and parsing imported files is counted in `resolve`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timing {
+    /// Parsing the source text or binary.
+    pub parse: Duration,
+    /// Resolving imports.
+    pub resolve: Duration,
+    /// Typechecking, including checking the type annotation.
+    pub typecheck: Duration,
+    /// Normalizing the expression.
+    pub normalize: Duration,
+    /// Converting the normalized expression to the requested Rust type.
+    pub deserialize: Duration,
+}
+
+impl Timing {
+    /// The total time spent.
+    pub fn total(&self) -> Duration {
+        self.parse
+            + self.resolve
+            + self.typecheck
+            + self.normalize
+            + self.deserialize
+    }
+}
+
+/// Records the duration of each stage into a `Timing`, if one was provided. Timing is opt-in
+/// because `Instant` panics on wasm, where `parse_with_timing()` is therefore not available.
+struct Stopwatch<'t> {
+    timing: Option<(&'t mut Timing, Instant)>,
+}
+
+impl<'t> Stopwatch<'t> {
+    fn new(timing: Option<&'t mut Timing>) -> Self {
+        Stopwatch {
+            timing: timing.map(|t| (t, Instant::now())),
+        }
+    }
+
+    /// Records the time elapsed since the previous lap into the given stage.
+    fn lap(&mut self, stage: fn(&mut Timing) -> &mut Duration) {
+        if let Some((timing, start)) = &mut self.timing {
+            let now = Instant::now();
+            *stage(timing) += now - *start;
+            *start = now;
+        }
+    }
+}
+
+/// A callback that receives each operation evaluated during normalization. See
+/// [`Deserializer::trace_normalization()`].
+#[derive(Clone)]
+struct TraceHook<'a>(Rc<dyn Fn(&str) + 'a>);
+
+impl<'a> TraceHook<'a> {
+    fn new(f: impl FnMut(&str) + 'a) -> Self {
+        let f = RefCell::new(f);
+        TraceHook(Rc::new(move |step| (f.borrow_mut())(step)))
+    }
+}
+
+impl std::fmt::Debug for TraceHook<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TraceHook")
+    }
+}
+
 /// Controls how a Dhall value is read.
 ///
 /// This builder exposes the ability to configure how a value is deserialized and what operations
@@ -59,6 +136,16 @@ pub struct Deserializer<'a, A> {
     source: Source<'a>,
     annot: A,
     allow_imports: bool,
+    canonicalize_paths: bool,
+    root_directory: Option<PathBuf>,
+    on_import: Option<ImportHook<'a>>,
+    env_vars: HashMap<String, String>,
+    env_file: Option<PathBuf>,
+    trace_normalization: Option<TraceHook<'a>>,
+    de_options: DeserializeOptions,
+    collect_errors: bool,
+    exact_type: Option<SimpleType>,
+    allow_extra_fields: bool,
     builtins: HashMap<dhall::syntax::Label, dhall::syntax::Expr>,
     // allow_remote_imports: bool,
     // use_cache: bool,
@@ -70,6 +157,16 @@ impl<'a> Deserializer<'a, NoAnnot> {
             source,
             annot: NoAnnot,
             allow_imports: true,
+            canonicalize_paths: false,
+            root_directory: None,
+            on_import: None,
+            env_vars: HashMap::new(),
+            env_file: None,
+            trace_normalization: None,
+            de_options: DeserializeOptions::default(),
+            collect_errors: false,
+            exact_type: None,
+            allow_extra_fields: false,
             builtins: HashMap::new(),
             // allow_remote_imports: true,
             // use_cache: true,
@@ -84,6 +181,11 @@ impl<'a> Deserializer<'a, NoAnnot> {
     fn from_binary_file<P: AsRef<Path>>(path: P) -> Self {
         Self::default_with_source(Source::BinaryFile(path.as_ref().to_owned()))
     }
+    fn from_files<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Self {
+        Self::default_with_source(Source::Files(
+            paths.into_iter().map(|p| p.as_ref().to_owned()).collect(),
+        ))
+    }
     // fn from_url(url: &'a str) -> Self {
     //     Self::default_with_source(Source::Url(url))
     // }
@@ -134,6 +236,16 @@ impl<'a> Deserializer<'a, NoAnnot> {
             annot: ManualAnnot(ty),
             source: self.source,
             allow_imports: self.allow_imports,
+            canonicalize_paths: self.canonicalize_paths,
+            root_directory: self.root_directory,
+            on_import: self.on_import,
+            env_vars: self.env_vars,
+            env_file: self.env_file,
+            trace_normalization: self.trace_normalization,
+            de_options: self.de_options,
+            collect_errors: self.collect_errors,
+            exact_type: self.exact_type,
+            allow_extra_fields: self.allow_extra_fields,
             builtins: self.builtins,
         }
     }
@@ -185,6 +297,16 @@ impl<'a> Deserializer<'a, NoAnnot> {
             annot: StaticAnnot,
             source: self.source,
             allow_imports: self.allow_imports,
+            canonicalize_paths: self.canonicalize_paths,
+            root_directory: self.root_directory,
+            on_import: self.on_import,
+            env_vars: self.env_vars,
+            env_file: self.env_file,
+            trace_normalization: self.trace_normalization,
+            de_options: self.de_options,
+            collect_errors: self.collect_errors,
+            exact_type: self.exact_type,
+            allow_extra_fields: self.allow_extra_fields,
             builtins: self.builtins,
         }
     }
@@ -219,6 +341,431 @@ impl<'a, A> Deserializer<'a, A> {
         }
     }
 
+    /// Sets whether to resolve symlinks in the paths of local imports.
+    ///
+    /// When enabled, a file reached through different paths is only loaded once and import cycles
+    /// going through symlinks are detected. Relative imports are then resolved from the
+    /// directory the file actually lives in, not from the directory of the symlink.
+    ///
+    /// By default, paths are not canonicalized.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = serde_dhall::from_file("config/linked.dhall")
+    ///     .canonicalize_paths(true)
+    ///     .parse::<u64>()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn canonicalize_paths(self, canonicalize_paths: bool) -> Self {
+        Deserializer {
+            canonicalize_paths,
+            ..self
+        }
+    }
+
+    /// Restricts local imports to files inside the given directory.
+    ///
+    /// Any local import that points outside of `root`, after resolving `..` components and
+    /// symlinks, fails with an error. This includes absolute paths and paths relative to the home
+    /// directory. The file passed to [`from_file()`] is not checked. Remote and environment
+    /// variable imports are not affected; disable imports with [`imports()`] to forbid them too.
+    ///
+    /// By default, local imports may point anywhere.
+    ///
+    /// [`from_file()`]: crate::from_file
+    /// [`imports()`]: Deserializer::imports
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let data = "/etc/passwd as Text";
+    /// assert!(
+    ///     serde_dhall::from_str(data)
+    ///         .root_directory("/srv/configs")
+    ///         .parse::<String>()
+    ///         .is_err()
+    /// );
+    /// ```
+    pub fn root_directory<P: AsRef<Path>>(self, root: P) -> Self {
+        Deserializer {
+            root_directory: Some(root.as_ref().to_owned()),
+            ..self
+        }
+    }
+
+    /// Sets the values of environment variables for `env:` imports.
+    ///
+    /// These take precedence over the environment of the process and over [`env_file()`], which
+    /// makes `env:` imports reproducible and easy to test. Other variables are still read from
+    /// the environment of the process.
+    ///
+    /// [`env_file()`]: Deserializer::env_file
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let port = serde_dhall::from_str("env:PORT")
+    ///     .env_vars(vec![("PORT".to_string(), "8080".to_string())])
+    ///     .parse::<u64>()?;
+    /// assert_eq!(port, 8080);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn env_vars(
+        mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Self {
+        self.env_vars.extend(vars);
+        self
+    }
+
+    /// Reads the values of environment variables for `env:` imports from a dotenv-style file.
+    ///
+    /// Each line of the file has the form `KEY=VALUE`, optionally preceded by `export`. Values
+    /// may be wrapped in single or double quotes. Blank lines and lines starting with `#` are
+    /// ignored. The file is read when parsing; parsing fails if it can't be read, or with the
+    /// line number of the first malformed line.
+    ///
+    /// These variables take precedence over the environment of the process, but not over
+    /// [`env_vars()`].
+    ///
+    /// [`env_vars()`]: Deserializer::env_vars
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// // build.env contains `VERSION="1.2.0"`
+    /// let version = serde_dhall::from_str("env:VERSION as Text")
+    ///     .env_file("build.env")
+    ///     .parse::<String>()?;
+    /// assert_eq!(version, "1.2.0");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn env_file<P: AsRef<Path>>(self, path: P) -> Self {
+        Deserializer {
+            env_file: Some(path.as_ref().to_owned()),
+            ..self
+        }
+    }
+
+    /// Sets whether to match record fields to struct fields ignoring case.
+    ///
+    /// Dhall labels are case-sensitive, so this is off by default. When enabled, a record with
+    /// two fields that only differ in case is an error. Type annotations are still checked
+    /// case-sensitively.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Config {
+    ///     port: u16,
+    ///     host_name: String,
+    /// }
+    ///
+    /// let config = serde_dhall::from_str("{ Port = 8080, HOST_NAME = \"localhost\" }")
+    ///     .with_field_case_insensitive(true)
+    ///     .parse::<Config>()?;
+    /// assert_eq!(config.port, 8080);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_field_case_insensitive(
+        mut self,
+        case_insensitive: bool,
+    ) -> Self {
+        self.de_options.field_case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Sets whether a `Natural` `0` or `1` may be deserialized into a Rust `bool`, as `false` or
+    /// `true` respectively. Any other `Natural` is then an error.
+    ///
+    /// This is not standard Dhall, so it is off by default. It has no effect on type annotations:
+    /// a `Natural` still doesn't match an expected `Bool` type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Config {
+    ///     verbose: bool,
+    /// }
+    ///
+    /// let config = serde_dhall::from_str("{ verbose = 1 }")
+    ///     .naturals_as_bools(true)
+    ///     .parse::<Config>()?;
+    /// assert!(config.verbose);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn naturals_as_bools(mut self, naturals_as_bools: bool) -> Self {
+        self.de_options.naturals_as_bools = naturals_as_bools;
+        self
+    }
+
+    /// Sets whether a value that isn't an `Optional` may be deserialized into a Rust `Option`, as
+    /// `Some`. This allows omitting the `Some` in front of values of optional fields.
+    ///
+    /// This is off by default. It has no effect on type annotations: a `Natural` still doesn't
+    /// match an expected `Optional Natural` type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Config {
+    ///     port: Option<u16>,
+    /// }
+    ///
+    /// let config = serde_dhall::from_str("{ port = 8080 }")
+    ///     .coerce_optional(true)
+    ///     .parse::<Config>()?;
+    /// assert_eq!(config.port, Some(8080));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn coerce_optional(mut self, coerce_optional: bool) -> Self {
+        self.de_options.coerce_optional = coerce_optional;
+        self
+    }
+
+    /// Sets whether a `Natural` or `Integer` may be deserialized into a Rust `f32` or `f64`.
+    ///
+    /// In Dhall `3` and `3.0` have different types, so by default only a `Double` can be read as a
+    /// float. It has no effect on type annotations: a `Natural` still doesn't match an expected
+    /// `Double` type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Config {
+    ///     ratio: f64,
+    /// }
+    ///
+    /// assert!(serde_dhall::from_str("{ ratio = 3 }")
+    ///     .parse::<Config>()
+    ///     .is_err());
+    /// let config = serde_dhall::from_str("{ ratio = 3 }")
+    ///     .numbers_as_doubles(true)
+    ///     .parse::<Config>()?;
+    /// assert_eq!(config.ratio, 3.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn numbers_as_doubles(mut self, numbers_as_doubles: bool) -> Self {
+        self.de_options.numbers_as_doubles = numbers_as_doubles;
+        self
+    }
+
+    /// Calls `hook` with each import before it is fetched, e.g. to log the files, environment
+    /// variables and URLs a configuration reads.
+    ///
+    /// The hook only observes imports; it can't change how they are resolved. It is also called
+    /// for imports that are found in the cache. The location of a local import is as it will be
+    /// read, i.e. after [`canonicalize_paths()`] applies.
+    ///
+    /// [`canonicalize_paths()`]: Deserializer::canonicalize_paths()
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::imports::ImportLocationKind;
+    ///
+    /// let mut imported = Vec::new();
+    /// let data = serde_dhall::from_str("env:HOME as Location")
+    ///     .on_import(|location, _mode| {
+    ///         if let ImportLocationKind::Env(name) = location.kind() {
+    ///             imported.push(name.clone());
+    ///         }
+    ///     })
+    ///     .parse::<serde_dhall::SimpleValue>()?;
+    /// assert_eq!(imported, vec!["HOME".to_string()]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_import(
+        self,
+        hook: impl FnMut(&ImportLocation, ImportMode) + 'a,
+    ) -> Self {
+        Deserializer {
+            on_import: Some(ImportHook::new(hook)),
+            ..self
+        }
+    }
+
+    /// Calls `hook` with each operation evaluated while normalizing the value, e.g. to find out
+    /// why a configuration evaluates to something unexpected.
+    ///
+    /// Each operation (function application, `merge`, field selection, arithmetic, ...) is
+    /// printed as written in the source, before its arguments are evaluated. Since evaluation is
+    /// lazy, only the operations needed to compute the result are reported, in the order they
+    /// were evaluated. The hook is called once normalization is complete, and tracing has no
+    /// cost when no hook is set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let mut steps = Vec::new();
+    /// let data = serde_dhall::from_str("let f = \\(x : Natural) -> x * 2 in f 3 + 1")
+    ///     .trace_normalization(|step| steps.push(step.to_owned()))
+    ///     .parse::<u64>()?;
+    /// assert_eq!(data, 7);
+    /// assert_eq!(steps, vec!["f 3 + 1", "f 3", "x * 2"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn trace_normalization(self, hook: impl FnMut(&str) + 'a) -> Self {
+        Deserializer {
+            trace_normalization: Some(TraceHook::new(hook)),
+            ..self
+        }
+    }
+
+    /// Sets whether to report all the type errors found instead of stopping at the first one.
+    ///
+    /// When enabled, independent errors in the fields of a record or the elements of a list are
+    /// all reported, and a type annotation is checked against each of them separately. Use
+    /// [`Error::errors()`] to iterate over them.
+    ///
+    /// By default, only the first error is reported.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use std::collections::HashMap;
+    /// use serde_dhall::{from_str, SimpleType};
+    ///
+    /// let ty = from_str("{ x: Natural, y: Natural }").parse::<SimpleType>()?;
+    /// let data = "{ x = True, y = \"foo\" }";
+    ///
+    /// let err = from_str(data)
+    ///     .type_annotation(&ty)
+    ///     .parse::<HashMap<String, u64>>()
+    ///     .unwrap_err();
+    /// assert_eq!(err.errors().count(), 1);
+    ///
+    /// let err = from_str(data)
+    ///     .type_annotation(&ty)
+    ///     .collect_errors(true)
+    ///     .parse::<HashMap<String, u64>>()
+    ///     .unwrap_err();
+    /// assert_eq!(err.errors().count(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::errors()`]: crate::Error::errors()
+    pub fn collect_errors(self, collect_errors: bool) -> Self {
+        Deserializer {
+            collect_errors,
+            ..self
+        }
+    }
+
+    /// Ensures that the type of the parsed expression is exactly the provided type.
+    ///
+    /// Unlike [`type_annotation()`], the expression is typechecked on its own and its inferred
+    /// type is then compared with `ty`. This catches a file becoming more or less specific than
+    /// expected, e.g. gaining an extra record field that deserialization would silently ignore.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use std::collections::HashMap;
+    /// use serde_dhall::{from_str, SimpleType};
+    ///
+    /// let ty = from_str("{ x: Natural }").parse::<SimpleType>()?;
+    ///
+    /// let data = from_str("{ x = 1 }")
+    ///     .assert_exact_type(&ty)
+    ///     .parse::<HashMap<String, u64>>()?;
+    /// assert_eq!(data.get("x"), Some(&1));
+    ///
+    /// assert!(
+    ///     from_str("{ x = 1, y = None Natural }")
+    ///         .assert_exact_type(&ty)
+    ///         .parse::<HashMap<String, u64>>()
+    ///         .is_err()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`type_annotation()`]: Deserializer::type_annotation()
+    pub fn assert_exact_type(self, ty: &SimpleType) -> Self {
+        Deserializer {
+            exact_type: Some(ty.clone()),
+            ..self
+        }
+    }
+
+    /// Sets whether records may have more fields than the type annotation expects.
+    ///
+    /// By default a type annotation must match the type of the parsed value exactly. With this
+    /// option, the value is also accepted if its type only differs from the annotation by extra
+    /// record fields, at any depth. The extra fields are then dropped before deserializing. This
+    /// lets old code read configuration files written for a newer version of a schema.
+    ///
+    /// Nothing else is relaxed: the fields of the annotation must all be present, even
+    /// `Optional` ones, and unions must have the same alternatives.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    /// use serde_dhall::StaticType;
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize, StaticType)]
+    /// struct Config {
+    ///     port: u16,
+    /// }
+    ///
+    /// let data = "{ port = 8080, log_level = \"debug\" }";
+    /// assert!(serde_dhall::from_str(data)
+    ///     .static_type_annotation()
+    ///     .parse::<Config>()
+    ///     .is_err());
+    ///
+    /// let config = serde_dhall::from_str(data)
+    ///     .static_type_annotation()
+    ///     .allow_extra_fields(true)
+    ///     .parse::<Config>()?;
+    /// assert_eq!(config, Config { port: 8080 });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn allow_extra_fields(self, allow_extra_fields: bool) -> Self {
+        Deserializer {
+            allow_extra_fields,
+            ..self
+        }
+    }
+
     // /// TODO
     // pub fn remote_imports(&mut self, imports: bool) -> &mut Self {
     //     self.allow_remote_imports = imports;
@@ -315,37 +862,218 @@ impl<'a, A> Deserializer<'a, A> {
         self
     }
 
-    fn _parse<T>(&self) -> dhall::error::Result<Result<Value>>
+    /// Returns a copy of this `Deserializer` that reads the given string instead, keeping all
+    /// the other settings.
+    ///
+    /// This makes it cheap to configure a `Deserializer` once and use it for many sources.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    /// use serde_dhall::StaticType;
+    ///
+    /// #[derive(Deserialize, StaticType, Debug, PartialEq)]
+    /// enum Level {
+    ///     Low,
+    ///     High,
+    /// }
+    ///
+    /// let template = serde_dhall::from_str("")
+    ///     .imports(false)
+    ///     .with_builtin_type("Level".to_string(), Level::static_type());
+    ///
+    /// let low = template.with_source_str("Level.Low").parse::<Level>()?;
+    /// let high = template.with_source_str("Level.High").parse::<Level>()?;
+    /// assert_eq!((low, high), (Level::Low, Level::High));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_source_str(&self, s: &'a str) -> Self
+    where
+        A: Clone,
+    {
+        Deserializer {
+            source: Source::Str(s),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this `Deserializer` that reads the given file instead, keeping all the
+    /// other settings. Imports will be resolved relative to the new file's path.
+    ///
+    /// See also [`with_source_str()`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use std::collections::HashMap;
+    ///
+    /// let template = serde_dhall::from_str("").canonicalize_paths(true);
+    /// for path in &["foo.dhall", "bar.dhall"] {
+    ///     let config: HashMap<String, u64> =
+    ///         template.with_source_file(path).parse()?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`with_source_str()`]: Deserializer::with_source_str()
+    pub fn with_source_file<P: AsRef<Path>>(&self, path: P) -> Self
+    where
+        A: Clone,
+    {
+        Deserializer {
+            source: Source::File(path.as_ref().to_owned()),
+            ..self.clone()
+        }
+    }
+
+    fn _parse<T>(
+        &self,
+        defaults: Option<&dhall::syntax::Expr>,
+        timing: Option<&mut Timing>,
+    ) -> dhall::error::Result<Result<Value>>
     where
         A: TypeAnnot,
         T: HasAnnot<A>,
     {
+        let mut stopwatch = Stopwatch::new(timing);
         Ctxt::with_new(|cx| {
             let parsed = match &self.source {
-                Source::Str(s) => Parsed::parse_str(s)?,
-                Source::File(p) => Parsed::parse_file(p.as_ref())?,
-                Source::BinaryFile(p) => Parsed::parse_binary_file(p.as_ref())?,
+                Source::Str(s) => vec![Parsed::parse_str(s)?],
+                Source::File(p) => vec![Parsed::parse_file(p.as_ref())?],
+                Source::BinaryFile(p) => {
+                    vec![Parsed::parse_binary_file(p.as_ref())?]
+                }
+                Source::Files(ps) => ps
+                    .iter()
+                    .map(|p| Parsed::parse_file(p.as_ref()))
+                    .collect::<dhall::error::Result<_>>()?,
             };
+            stopwatch.lap(|t| &mut t.parse);
 
-            let parsed_with_builtins =
-                self.builtins.iter().fold(parsed, |acc, (name, subst)| {
-                    acc.add_let_binding(name.clone(), subst.clone())
-                });
-
-            let resolved = if self.allow_imports {
-                parsed_with_builtins.resolve(cx)?
+            let opts = if self.allow_imports {
+                let mut env_vars = self.env_vars.clone();
+                if let Some(path) = &self.env_file {
+                    match read_env_file(path) {
+                        Ok(vars) => {
+                            for (name, value) in vars {
+                                env_vars.entry(name).or_insert(value);
+                            }
+                        }
+                        Err(e) => return Ok(Err(e)),
+                    }
+                }
+                Some(ResolveOptions {
+                    canonicalize_paths: self.canonicalize_paths,
+                    root_directory: self.root_directory.clone(),
+                    on_import: self.on_import.clone(),
+                    env_vars,
+                })
             } else {
-                parsed_with_builtins.skip_resolve(cx)?
+                None
             };
-            let typed = match &T::get_annot(self.annot) {
+
+            // Each file is resolved on its own, so that its imports are relative to it, and the
+            // results are then merged.
+            let mut resolved = None;
+            for (i, parsed) in parsed.into_iter().enumerate() {
+                let parsed_with_builtins =
+                    self.builtins.iter().fold(parsed, |acc, (name, subst)| {
+                        acc.add_let_binding(name.clone(), subst.clone())
+                    });
+                let parsed_with_builtins = match defaults {
+                    Some(defaults) if i == 0 => {
+                        parsed_with_builtins.prefer_over(defaults.clone())
+                    }
+                    _ => parsed_with_builtins,
+                };
+                let file = match &opts {
+                    Some(opts) => {
+                        parsed_with_builtins.resolve_with_options(cx, opts)?
+                    }
+                    None => parsed_with_builtins.skip_resolve(cx)?,
+                };
+                resolved = Some(match resolved {
+                    Some(base) => file.prefer_over(base),
+                    None => file,
+                });
+            }
+            let resolved = match resolved {
+                Some(resolved) => resolved,
+                None => {
+                    return Ok(Err(Error(ErrorKind::Deserialize(
+                        "no files to read".to_owned(),
+                    ))))
+                }
+            };
+            stopwatch.lap(|t| &mut t.resolve);
+            let annot = T::get_annot(self.annot);
+            // With `allow_extra_fields`, the value is typechecked on its own first, to find out
+            // whether its type only has more fields than the annotation.
+            let mut is_relaxed = false;
+            let typed = match &annot {
+                Some(ty) if self.allow_extra_fields => {
+                    let typed = if self.collect_errors {
+                        resolved.typecheck_collecting_errors(cx, None)?
+                    } else {
+                        resolved.typecheck(cx)?
+                    };
+                    is_relaxed = match SimpleType::from_nir(typed.ty().as_nir())
+                    {
+                        Ok(inferred) => {
+                            inferred != *ty && inferred.has_extra_fields(ty)
+                        }
+                        Err(_) => false,
+                    };
+                    if !is_relaxed {
+                        typed.check_type(cx, &ty.to_hir())?;
+                    }
+                    typed
+                }
+                _ if self.collect_errors => {
+                    let ty = annot.as_ref().map(|ty| ty.to_hir());
+                    resolved.typecheck_collecting_errors(cx, ty.as_ref())?
+                }
                 None => resolved.typecheck(cx)?,
                 Some(ty) => resolved.typecheck_with(cx, &ty.to_hir())?,
             };
-            Ok(Value::from_nir_and_ty(
+            if let Some(expected) = &self.exact_type {
+                let ty = typed.ty().as_nir();
+                if SimpleType::from_nir(ty).ok().as_ref() != Some(expected) {
+                    let inferred =
+                        ty.to_hir_noenv().to_expr(cx, Default::default());
+                    return Ok(Err(Error(ErrorKind::TypeNotExact {
+                        expected: expected.clone(),
+                        inferred: inferred.to_string(),
+                    })));
+                }
+            }
+            stopwatch.lap(|t| &mut t.typecheck);
+            if self.trace_normalization.is_some() {
+                cx.start_normalization_trace();
+            }
+            let normalized = typed.normalize(cx);
+            stopwatch.lap(|t| &mut t.normalize);
+            let val = Value::from_nir_and_ty(
                 cx,
-                typed.normalize(cx).as_nir(),
+                normalized.as_nir(),
                 typed.ty().as_nir(),
-            ))
+            );
+            let val = match &annot {
+                Some(ty) if is_relaxed => val.map(|val| val.restrict_to(ty)),
+                _ => val,
+            };
+            stopwatch.lap(|t| &mut t.deserialize);
+            if let Some(hook) = &self.trace_normalization {
+                for step in cx.take_normalization_trace() {
+                    (hook.0)(&step.to_string());
+                }
+            }
+            Ok(val)
         })
     }
 
@@ -370,14 +1098,384 @@ impl<'a, A> Deserializer<'a, A> {
         A: TypeAnnot,
         T: FromDhall + HasAnnot<A>,
     {
+        let val = self._parse::<T>(None, None).map_err(Error::from)??;
+        T::from_dhall_with_options(&val, self.de_options)
+    }
+
+    /// Parses the chosen dhall value with a [`DeserializeSeed`], to pass some state to the
+    /// deserialization.
+    ///
+    /// If you enabled static annotations, the value produced by the seed is required to
+    /// implement [`StaticType`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::de::{Deserialize, DeserializeSeed, Deserializer};
+    ///
+    /// /// Reads a list of names, replacing each one by its index in a shared pool.
+    /// struct Interner<'a>(&'a mut Vec<String>);
+    ///
+    /// impl<'de> DeserializeSeed<'de> for Interner<'_> {
+    ///     type Value = Vec<usize>;
+    ///     fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Vec<usize>, D::Error> {
+    ///         let names = Vec::<String>::deserialize(d)?;
+    ///         let pool = self.0;
+    ///         Ok(names
+    ///             .into_iter()
+    ///             .map(|name| match pool.iter().position(|n| *n == name) {
+    ///                 Some(i) => i,
+    ///                 None => {
+    ///                     pool.push(name);
+    ///                     pool.len() - 1
+    ///                 }
+    ///             })
+    ///             .collect())
+    ///     }
+    /// }
+    ///
+    /// let mut pool = Vec::new();
+    /// let ids = serde_dhall::from_str(r#"[ "a", "b", "a" ]"#)
+    ///     .parse_seed(Interner(&mut pool))?;
+    /// assert_eq!(ids, vec![0, 1, 0]);
+    /// assert_eq!(pool, vec!["a", "b"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`DeserializeSeed`]: serde::de::DeserializeSeed
+    /// [`StaticType`]: crate::StaticType
+    pub fn parse_seed<S, V>(&self, seed: S) -> Result<V>
+    where
+        A: TypeAnnot,
+        S: for<'de> serde::de::DeserializeSeed<'de, Value = V>,
+        V: HasAnnot<A>,
+    {
+        let val = self._parse::<V>(None, None).map_err(Error::from)??;
+        crate::deserialize::from_dhall_seed(&val, self.de_options, seed)
+    }
+
+    /// Parses the chosen dhall value as a list, and returns an iterator that deserializes its
+    /// elements one at a time.
+    ///
+    /// The whole list is still evaluated up front, but the elements aren't collected into a
+    /// `Vec<T>`, so they can be processed or passed on one by one. If you enabled static
+    /// annotations, the list is checked against the type `List T`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let squares = serde_dhall::from_str("[ 1, 4, 9 ]").parse_list_iter::<u64>()?;
+    /// let mut total = 0;
+    /// for square in squares {
+    ///     total += square?;
+    /// }
+    /// assert_eq!(total, 14);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse_list_iter<T>(&self) -> Result<impl Iterator<Item = Result<T>>>
+    where
+        A: TypeAnnot,
+        T: FromDhall,
+        Vec<T>: HasAnnot<A>,
+    {
+        let val = self._parse::<Vec<T>>(None, None).map_err(Error::from)??;
+        let xs = val.into_list_elements().ok_or_else(|| {
+            Error(ErrorKind::Deserialize("expected a list".to_owned()))
+        })?;
+        let opts = self.de_options;
+        Ok(xs.into_iter().enumerate().map(move |(i, x)| {
+            T::from_dhall_with_options(&x, opts)
+                .map_err(|e| e.prefix_path(&format!("[{}]", i)))
+        }))
+    }
+
+    /// Parses the chosen dhall value like [`parse()`], and reports how long each stage took.
+    ///
+    /// This is useful to find out whether a slow configuration spends its time in fetching
+    /// imports, typechecking or normalization.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let (data, timing) = serde_dhall::from_str("6 * 7").parse_with_timing::<u64>()?;
+    /// assert_eq!(data, 42);
+    /// println!("normalization took {:?}", timing.normalize);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// This is not available on wasm, where the time can't be measured.
+    ///
+    /// [`parse()`]: Deserializer::parse()
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn parse_with_timing<T>(&self) -> Result<(T, Timing)>
+    where
+        A: TypeAnnot,
+        T: FromDhall + HasAnnot<A>,
+    {
+        let mut timing = Timing::default();
         let val = self
-            ._parse::<T>()
-            .map_err(ErrorKind::Dhall)
-            .map_err(Error)??;
-        T::from_dhall(&val)
+            ._parse::<T>(None, Some(&mut timing))
+            .map_err(Error::from)??;
+        let start = Instant::now();
+        let val = T::from_dhall_with_options(&val, self.de_options)?;
+        timing.deserialize += start.elapsed();
+        Ok((val, timing))
+    }
+
+    /// Parses the chosen dhall value, merged over the provided defaults.
+    ///
+    /// `defaults` is serialized to a Dhall record, and the parsed record is merged over it with
+    /// the `//` operator before deserializing the result. Any field missing from the input thus
+    /// keeps its default value. The merge is shallow: a nested record in the input replaces the
+    /// corresponding default entirely.
+    ///
+    /// If a type annotation is set, it is checked against the merged record.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    /// struct Config {
+    ///     port: u16,
+    ///     verbose: bool,
+    /// }
+    ///
+    /// let defaults = Config { port: 80, verbose: false };
+    /// let config = serde_dhall::from_str("{ verbose = True }")
+    ///     .parse_with_defaults(defaults)?;
+    /// assert_eq!(config, Config { port: 80, verbose: true });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse_with_defaults<T>(&self, defaults: T) -> Result<T>
+    where
+        A: TypeAnnot,
+        T: FromDhall + ToDhall + HasAnnot<A>,
+    {
+        let annot = T::get_annot(self.annot);
+        let defaults = defaults.to_dhall(annot.as_ref())?.to_expr();
+        let val = self
+            ._parse::<T>(Some(&defaults), None)
+            .map_err(Error::from)??;
+        T::from_dhall_with_options(&val, self.de_options)
+    }
+
+    /// Parses the chosen dhall value like [`parse()`], falling back to `T::default()` on failure.
+    ///
+    /// The full pipeline still runs; only if some stage fails is the default value returned,
+    /// together with the error that occurred so that it can be logged. On success the error is
+    /// `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[derive(Debug, PartialEq, Default, serde::Deserialize)]
+    /// struct Config {
+    ///     port: u16,
+    /// }
+    ///
+    /// let (config, err) = serde_dhall::from_str("{ port = 8080 }")
+    ///     .parse_or_default::<Config>();
+    /// assert_eq!(config, Config { port: 8080 });
+    /// assert!(err.is_none());
+    ///
+    /// let (config, err) = serde_dhall::from_file("missing.dhall")
+    ///     .parse_or_default::<Config>();
+    /// assert_eq!(config, Config::default());
+    /// assert!(err.is_some());
+    /// ```
+    ///
+    /// [`parse()`]: Deserializer::parse()
+    pub fn parse_or_default<T>(&self) -> (T, Option<Error>)
+    where
+        A: TypeAnnot,
+        T: FromDhall + HasAnnot<A> + Default,
+    {
+        match self.parse::<T>() {
+            Ok(val) => (val, None),
+            Err(err) => (T::default(), Some(err)),
+        }
+    }
+
+    /// Parses the chosen dhall value, leaving its deserialization for later.
+    ///
+    /// Parts of the returned [`LazyValue`] can then be deserialized one at a time with
+    /// [`LazyValue::get()`]. This is useful to read a few fields out of a large configuration
+    /// without defining Rust types for the whole of it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = serde_dhall::from_str("{ x = { y = [1, 2] }, z = True }")
+    ///     .parse_lazy()?;
+    /// assert_eq!(data.get::<Vec<u64>>("x.y")?, vec![1, 2]);
+    /// assert_eq!(data.get::<bool>("z")?, true);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`LazyValue::get()`]: crate::LazyValue::get()
+    pub fn parse_lazy(&self) -> Result<LazyValue>
+    where
+        A: TypeAnnot,
+        Value: HasAnnot<A>,
+    {
+        Ok(LazyValue::new(self.parse::<Value>()?, self.de_options))
+    }
+
+    /// Parses the chosen dhall value as a record, and deserializes each of its fields as `T`.
+    ///
+    /// Unlike `parse::<HashMap<String, T>>()` with a type annotation, this does not require the
+    /// fields to share the same Dhall type: each field is deserialized on its own, so they only
+    /// need to each be a valid `T`. This is useful to read a record of different unions into a
+    /// single Rust enum.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// enum Setting {
+    ///     Port(u16),
+    ///     Host(String),
+    ///     Debug,
+    /// }
+    ///
+    /// let settings = serde_dhall::from_str(
+    ///     "{ a = < Port : Natural >.Port 80, \
+    ///        b = < Host : Text | Debug >.Host \"localhost\", \
+    ///        c = < Debug >.Debug }",
+    /// )
+    /// .parse_all_fields_as::<Setting>()?;
+    ///
+    /// assert_eq!(settings["a"], Setting::Port(80));
+    /// assert_eq!(settings["b"], Setting::Host("localhost".to_owned()));
+    /// assert_eq!(settings["c"], Setting::Debug);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse_all_fields_as<T>(&self) -> Result<HashMap<String, T>>
+    where
+        A: TypeAnnot,
+        Value: HasAnnot<A>,
+        T: FromDhall,
+    {
+        let val = self.parse::<Value>()?;
+        let fields = val.record_fields().ok_or_else(|| {
+            Error(ErrorKind::Deserialize(format!(
+                "expected a record, found: {}",
+                val
+            )))
+        })?;
+        let mut map = HashMap::with_capacity(fields.len());
+        for (k, v) in fields {
+            let x = T::from_dhall_with_options(&v, self.de_options)?;
+            map.insert(k, x);
+        }
+        Ok(map)
+    }
+
+    /// Parses a Dhall schema, i.e. a record with a `Type` field and a `default` field, as used
+    /// with the `::` operator.
+    ///
+    /// Returns the type and the default values. The default values are usually a record with
+    /// some of the fields of the type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use std::collections::HashMap;
+    /// use serde_dhall::SimpleType;
+    ///
+    /// let (ty, default) = serde_dhall::from_str(
+    ///     "{ Type = { port : Natural, host : Text }, default.port = 80 }",
+    /// )
+    /// .parse_schema()?;
+    ///
+    /// assert_eq!(ty, serde_dhall::from_str("{ port : Natural, host : Text }").parse()?);
+    /// let default: HashMap<String, u64> = default.deserialize()?;
+    /// assert_eq!(default.get("port"), Some(&80));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse_schema(&self) -> Result<(SimpleType, Value)>
+    where
+        A: TypeAnnot,
+        Value: HasAnnot<A>,
+    {
+        let val = self.parse::<Value>()?;
+        let field = |name: &str| {
+            val.get_path(name).map_err(|_| {
+                Error(ErrorKind::Deserialize(format!(
+                    "expected a schema with `Type` and `default` fields, but \
+                     the `{}` field is missing",
+                    name
+                )))
+            })
+        };
+        let ty = field("Type")?.deserialize::<SimpleType>().map_err(|_| {
+            Error(ErrorKind::Deserialize(
+                "the `Type` field of a schema must be a simple type".to_owned(),
+            ))
+        })?;
+        let default = field("default")?;
+        Ok((ty, default))
     }
 }
 
+/// Reads `KEY=VALUE` lines from a dotenv-style file.
+fn read_env_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let err = |msg: String| Error(ErrorKind::Deserialize(msg));
+    let text = std::fs::read_to_string(path).map_err(|e| {
+        err(format!("cannot read env file {}: {}", path.display(), e))
+    })?;
+    let mut vars = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let var = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = match var.find('=') {
+            Some(eq) => (var[..eq].trim(), var[eq + 1..].trim()),
+            None => ("", ""),
+        };
+        let valid_name = !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(err(format!(
+                "{}:{}: expected `KEY=VALUE`, found `{}`",
+                path.display(),
+                i + 1,
+                line
+            )));
+        }
+        let is_quoted = value.len() >= 2
+            && (value.starts_with('"') && value.ends_with('"')
+                || value.starts_with('\'') && value.ends_with('\''));
+        let value = if is_quoted {
+            &value[1..value.len() - 1]
+        } else {
+            value
+        };
+        vars.push((name.to_owned(), value.to_owned()));
+    }
+    Ok(vars)
+}
+
 /// Deserialize a value from a string of Dhall text.
 ///
 /// This returns a [`Deserializer`] object. Call the [`parse()`] method to get the deserialized
@@ -481,6 +1579,71 @@ pub fn from_binary_file<'a, P: AsRef<Path>>(
     Deserializer::from_binary_file(path)
 }
 
+/// Deserialize a value from several Dhall files, merged with `//` in order: a field of a later
+/// file overrides the same field of the earlier ones. The files must all be records.
+///
+/// This returns a [`Deserializer`] object. Call the [`parse()`] method to get the deserialized
+/// value, or use other [`Deserializer`] methods to control the deserialization process. A type
+/// annotation applies to the merged record.
+///
+/// Imports will be resolved relative to the path of the file they appear in.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> serde_dhall::Result<()> {
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Config {
+///     host: String,
+///     port: u16,
+/// }
+///
+/// // `override.dhall` can set only the fields that differ from `base.dhall`.
+/// let config: Config =
+///     serde_dhall::from_files(&["base.dhall", "override.dhall"]).parse()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`parse()`]: Deserializer::parse()
+pub fn from_files<'a, P: AsRef<Path>>(
+    paths: impl IntoIterator<Item = P>,
+) -> Deserializer<'a, NoAnnot> {
+    Deserializer::from_files(paths)
+}
+
+/// Checks that a string of Dhall text has the given type, without evaluating or deserializing it.
+///
+/// Imports are resolved relative to the current directory. This is useful to lint configuration
+/// files against a schema; use [`from_str()`] with [`type_annotation()`] to also read the value.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> serde_dhall::Result<()> {
+/// use serde_dhall::SimpleType;
+///
+/// let schema: SimpleType = serde_dhall::from_str("{ port : Natural }").parse()?;
+///
+/// assert!(serde_dhall::validate("{ port = 8000 + 80 }", &schema).is_ok());
+/// assert!(serde_dhall::validate("{ port = \"80\" }", &schema).is_err());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`type_annotation()`]: Deserializer::type_annotation()
+pub fn validate(source: &str, ty: &SimpleType) -> Result<()> {
+    Ctxt::with_new(|cx| {
+        let resolved = Parsed::parse_str(source)?.resolve(cx)?;
+        resolved
+            .typecheck_with(cx, &ty.to_hir())
+            .map_err(dhall::error::Error::from)?;
+        Ok(())
+    })
+}
+
 // pub fn from_url(url: &str) -> Deserializer<'_, NoAnnot> {
 //     Deserializer::from_url(url)
 // }
@@ -13,6 +13,9 @@ use crate::{Result, SimpleType, ToDhall};
 /// Note that if you do not provide a type annotation, some values may not be convertible to Dhall,
 /// like empty lists or enums.
 ///
+/// Maps and structs are written as records, whose fields are always sorted by name. The output
+/// therefore doesn't depend on the iteration order of a `HashMap`.
+///
 /// [`to_string()`]: Serializer::to_string()
 ///
 /// # Examples
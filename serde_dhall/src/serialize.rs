@@ -57,6 +57,13 @@ where
 #[derive(Default, Clone, Copy)]
 struct Serializer;
 
+impl SimpleType {
+    /// Encodes this type as a value, in the shape given by its `Serialize` impl.
+    pub(crate) fn to_simple_value(&self) -> Result<SimpleValue> {
+        ser::Serialize::serialize(self, Serializer)
+    }
+}
+
 impl ser::Serializer for Serializer {
     type Ok = SimpleValue;
     type Error = Error;
@@ -140,15 +147,12 @@ impl ser::Serializer for Serializer {
     fn serialize_newtype_struct<T>(
         self,
         _name: &'static str,
-        _value: &T,
+        value: &T,
     ) -> Result<Self::Ok>
     where
         T: ?Sized + ser::Serialize,
     {
-        Err(ErrorKind::Serialize(
-            "Unsupported data for serialization: newtype struct".to_owned(),
-        )
-        .into())
+        value.serialize(self)
     }
     fn serialize_struct(
         self,
@@ -33,6 +33,26 @@ use crate::SimpleType;
 /// # Ok(())
 /// # }
 /// ```
+///
+/// The fields of a `#[serde(flatten)]` field are added to the record of the struct, so the
+/// flattened field must have a record type. The derive rejects types that are obviously not
+/// records, and `static_type()` panics on the others, or if two flattened records share a field.
+///
+/// ```compile_fail
+/// use serde::Deserialize;
+/// use serde_dhall::StaticType;
+///
+/// #[derive(Deserialize, StaticType)]
+/// struct Inner {
+///     x: bool,
+/// }
+///
+/// #[derive(Deserialize, StaticType)]
+/// struct Foo {
+///     #[serde(flatten)]
+///     inner: Option<Inner>,
+/// }
+/// ```
 pub trait StaticType {
     /// Return the Dhall type that represents this type.
     ///
@@ -43,22 +63,29 @@ pub trait StaticType {
     /// use serde::Deserialize;
     /// use serde_dhall::{SimpleType, StaticType};
     ///
-    /// // Using `derive(StaticType)` here would give it the type `{ _1: List Natural }`.
+    /// // Using `derive(StaticType)` here would give it the type `Natural`, but it is read from a
+    /// // list.
     /// #[derive(Deserialize)]
-    /// #[serde(transparent)]
-    /// struct Foo(Vec<u64>);
+    /// #[serde(from = "Vec<u64>")]
+    /// struct Total(u64);
+    ///
+    /// impl From<Vec<u64>> for Total {
+    ///     fn from(xs: Vec<u64>) -> Total {
+    ///         Total(xs.into_iter().sum())
+    ///     }
+    /// }
     ///
-    /// impl StaticType for Foo {
+    /// impl StaticType for Total {
     ///     fn static_type() -> SimpleType {
     ///         SimpleType::List(Box::new(SimpleType::Natural))
     ///     }
     /// }
     ///
-    /// let foo = serde_dhall::from_str("[ 1, 2 ]")
+    /// let total = serde_dhall::from_str("[ 1, 2 ]")
     ///     .static_type_annotation()
-    ///     .parse::<Foo>()?;
+    ///     .parse::<Total>()?;
     ///
-    /// assert_eq!(foo.0, vec![1, 2]);
+    /// assert_eq!(total.0, 3);
     /// # Ok(())
     /// # }
     /// ```
@@ -86,6 +113,7 @@ derive_builtin!(i32, Integer);
 derive_builtin!(f64, Double);
 derive_builtin!(f32, Double);
 derive_builtin!(String, Text);
+derive_builtin!(char, Text);
 derive_builtin!(&str, Text);
 
 impl StaticType for () {
@@ -199,6 +227,24 @@ where
     }
 }
 
+impl<T> StaticType for std::collections::HashSet<T>
+where
+    T: StaticType,
+{
+    fn static_type() -> SimpleType {
+        SimpleType::List(Box::new(T::static_type()))
+    }
+}
+
+impl<T> StaticType for std::collections::BTreeSet<T>
+where
+    T: StaticType,
+{
+    fn static_type() -> SimpleType {
+        SimpleType::List(Box::new(T::static_type()))
+    }
+}
+
 impl<'a, T> StaticType for &'a T
 where
     T: StaticType,
@@ -207,3 +253,30 @@ where
         T::static_type()
     }
 }
+
+impl<T> StaticType for Box<T>
+where
+    T: StaticType,
+{
+    fn static_type() -> SimpleType {
+        T::static_type()
+    }
+}
+
+impl<T> StaticType for std::rc::Rc<T>
+where
+    T: StaticType,
+{
+    fn static_type() -> SimpleType {
+        T::static_type()
+    }
+}
+
+impl<T> StaticType for std::sync::Arc<T>
+where
+    T: StaticType,
+{
+    fn static_type() -> SimpleType {
+        T::static_type()
+    }
+}
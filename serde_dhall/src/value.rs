@@ -1,3 +1,4 @@
+use serde::Serialize;
 use std::collections::{BTreeMap, HashMap};
 use std::result::Result as StdResult;
 
@@ -8,13 +9,18 @@ pub use dhall::syntax::NumKind;
 use dhall::syntax::{Expr, ExprKind, Span};
 use dhall::Ctxt;
 
+use crate::deserialize::DeserializeOptions;
 use crate::{Error, ErrorKind, FromDhall, Result, ToDhall};
 
 #[derive(Debug, Clone)]
-enum ValueKind {
+pub(crate) enum ValueKind {
     /// Invariant: the value must be printable with the given type.
     Val(SimpleValue, Option<SimpleType>),
     Ty(SimpleType),
+    /// A record that contains types, like `{ Port = Natural, Host = Text }` or
+    /// `{ Type = { port : Natural }, default = { port = 80 } }`.
+    /// Invariant: some field is a `Ty` or `TyRecord`.
+    TyRecord(BTreeMap<String, Value>),
 }
 
 #[doc(hidden)]
@@ -137,18 +143,24 @@ pub enum SimpleValue {
 /// `Bool`  | `bool`
 /// `Natural`  | `u64`, `u32`, ...
 /// `Integer`  | `i64`, `i32`, ...
-/// `Double`  | `f64`, `f32`, ...
-/// `Text`  | `String`
-/// `List T`  | `Vec<T>`
+/// `Double`  | `f64`, `f32`, ... (`NaN` and infinities are kept; finite values outside of the range of `f32` are an error)
+/// `Text`  | `String`, `char` (the text must be a single character)
+/// `List T`  | `Vec<T>`, `HashSet<T>`, `BTreeSet<T>` (duplicate elements are dropped)
 /// `Optional T`  | `Option<T>`
 /// `{ x: T, y: U }`  | structs
 /// `{ _1: T, _2: U }`  | `(T, U)`, structs
+/// `T`  | newtype structs like `struct Foo(T)`
 /// `{ x: T, y: T }`  | `HashMap<String, T>`, structs
 /// `< x: T \| y: U >`  | enums
+/// `< x \| y >`  | enums, `String`
+/// `< Ok: T \| Err: E >`  | `Result<T, E>` (the alternatives must be named `Ok` and `Err`)
+/// `{ tag: Text, contents: T }`  | enums with `#[serde(tag = "tag", content = "contents")]`, omitting `contents` for unit variants
 /// `Prelude.Map.Type Text T`  | `HashMap<String, T>`, structs
 /// `T -> U`  | unsupported
 /// `Prelude.JSON.Type`  | unsupported
-/// `Prelude.Map.Type T U`  | unsupported
+/// `Prelude.Map.Type T U`  | `HashMap<T, U>`
+///
+/// `Box<T>`, `Rc<T>` and `Arc<T>` correspond to the same Dhall type as `T`.
 ///
 /// # Examples
 ///
@@ -185,7 +197,10 @@ pub enum SimpleValue {
 /// # Ok(())
 /// # }
 /// ```
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// `SimpleType` implements serde's `Serialize` and `Deserialize`, so a parsed type can be
+/// stored in any serde format and loaded back without re-parsing the Dhall schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum SimpleType {
     /// Corresponds to the Dhall type `Bool`
     Bool,
@@ -208,52 +223,439 @@ pub enum SimpleType {
 }
 
 impl Value {
+    /// Deserializes this value into a Rust type, without parsing the Dhall source again.
+    ///
+    /// This is useful to inspect a value before deciding which type to convert it to.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    /// use serde_dhall::Value;
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Point {
+    ///     x: u64,
+    ///     y: u64,
+    /// }
+    ///
+    /// let value: Value = serde_dhall::from_str("{ x = 1, y = 2 }").parse()?;
+    /// let point: Point = value.deserialize()?;
+    ///
+    /// assert_eq!(point, Point { x: 1, y: 2 });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn deserialize<T: FromDhall>(&self) -> Result<T> {
+        T::from_dhall(self)
+    }
+
+    /// Returns the Dhall type of this value, e.g. to discover the schema of a configuration file.
+    ///
+    /// This is `None` when the value is itself a type or a record of types, since their type
+    /// isn't a [`SimpleType`]. It is also `None` for values built without a type, e.g. by
+    /// serializing a Rust value without a type annotation.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::{SimpleType, Value};
+    ///
+    /// let value: Value = serde_dhall::from_str("{ x = 1, y = [] : List Text }").parse()?;
+    /// let ty: SimpleType = serde_dhall::from_str("{ x : Natural, y : List Text }").parse()?;
+    /// assert_eq!(value.type_of(), Some(ty));
+    ///
+    /// let value: Value = serde_dhall::from_str("Natural").parse()?;
+    /// assert_eq!(value.type_of(), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn type_of(&self) -> Option<SimpleType> {
+        match &self.kind {
+            ValueKind::Val(_, ty) => ty.clone(),
+            ValueKind::Ty(_) | ValueKind::TyRecord(_) => None,
+        }
+    }
+
+    /// Writes the Dhall representation of this value to `w`.
+    ///
+    /// This produces the same text as the `Display` impl, but streams it to the writer instead of
+    /// building a `String` first, which is preferable for large values.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use serde_dhall::Value;
+    ///
+    /// let value: Value = serde_dhall::from_str("{ x = 1, y = [ True ] }").parse()?;
+    ///
+    /// let mut out = Vec::new();
+    /// value.write_dhall(&mut out)?;
+    ///
+    /// assert_eq!(String::from_utf8(out)?, value.to_string());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_dhall(
+        &self,
+        w: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
+        write!(w, "{}", self.to_expr())
+    }
+
+    /// Compares two values like `==`, except that `Double`s are considered equal when they differ
+    /// by at most `epsilon`. This is useful to compare doubles computed by arithmetic that doesn't
+    /// reproduce the same bits.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::Value;
+    ///
+    /// let a: Value = serde_dhall::from_str("{ x = 0.3, y = \"a\" }").parse()?;
+    /// let b: Value = serde_dhall::from_str("{ x = 0.30000000000000004, y = \"a\" }").parse()?;
+    ///
+    /// assert_ne!(a, b);
+    /// assert!(a.approx_eq(&b, 1e-9));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn approx_eq(&self, other: &Value, epsilon: f64) -> bool {
+        use ValueKind::*;
+        match (&self.kind, &other.kind) {
+            (Val(a, _), Val(b, _)) => a.approx_eq(b, epsilon),
+            (Ty(a), Ty(b)) => a == b,
+            (TyRecord(a), TyRecord(b)) => {
+                a.len() == b.len()
+                    && a.iter().zip(b).all(|((ka, va), (kb, vb))| {
+                        ka == kb && va.approx_eq(vb, epsilon)
+                    })
+            }
+            _ => false,
+        }
+    }
+
     pub(crate) fn from_nir_and_ty<'cx>(
         cx: Ctxt<'cx>,
         x: &Nir<'cx>,
         ty: &Nir<'cx>,
     ) -> Result<Self> {
-        Ok(if let Ok(val) = SimpleValue::from_nir(x) {
-            // The type must be simple if the value is simple.
-            let ty = SimpleType::from_nir(ty).unwrap();
-            Value {
-                kind: ValueKind::Val(val, Some(ty)),
+        match Self::try_from_nir_and_ty(x, ty) {
+            Some(val) => Ok(val),
+            None => {
+                let expr = x.to_hir_noenv().to_expr(cx, Default::default());
+                Err(Error(ErrorKind::Deserialize(format!(
+                    "this is neither a simple type nor a simple value: {}",
+                    expr
+                ))))
             }
+        }
+    }
+
+    /// Converts a simple value, a simple type, or a (possibly nested) record of those.
+    fn try_from_nir_and_ty(x: &Nir, ty: &Nir) -> Option<Self> {
+        // A simple value can still have a non-simple type, e.g. `[] : List (Bool -> Bool)`.
+        let kind = if let (Ok(val), Ok(ty)) =
+            (SimpleValue::from_nir(x), SimpleType::from_nir(ty))
+        {
+            ValueKind::Val(val, Some(ty))
         } else if let Ok(ty) = SimpleType::from_nir(x) {
-            Value {
-                kind: ValueKind::Ty(ty),
-            }
+            ValueKind::Ty(ty)
+        } else if let (NirKind::RecordLit(kvs), NirKind::RecordType(kts)) =
+            (x.kind(), ty.kind())
+        {
+            ValueKind::TyRecord(
+                kvs.iter()
+                    .map(|(k, v)| {
+                        let val = Self::try_from_nir_and_ty(v, kts.get(k)?)?;
+                        Some((k.into(), val))
+                    })
+                    .collect::<Option<_>>()?,
+            )
         } else {
-            let expr = x.to_hir_noenv().to_expr(cx, Default::default());
-            return Err(Error(ErrorKind::Deserialize(format!(
-                "this is neither a simple type nor a simple value: {}",
-                expr
-            ))));
+            return None;
+        };
+        Some(Value { kind })
+    }
+
+    pub(crate) fn kind(&self) -> &ValueKind {
+        &self.kind
+    }
+
+    /// Gets the value at the given dotted path of record fields. The empty path refers to the
+    /// whole value.
+    pub(crate) fn get_path(&self, path: &str) -> Result<Value> {
+        let (mut val, mut ty) = match &self.kind {
+            ValueKind::Val(val, ty) => (val, ty.as_ref()),
+            ValueKind::TyRecord(kvs) => {
+                let mut fields = path.splitn(2, '.');
+                let field = fields.next().unwrap_or("");
+                let rest = fields.next().unwrap_or("");
+                if field.is_empty() {
+                    return Ok(self.clone());
+                }
+                return match kvs.get(field) {
+                    Some(v) => v.get_path(rest),
+                    None => Err(Error(ErrorKind::Deserialize(format!(
+                        "no field `{}` found at path `{}`",
+                        field, path
+                    )))),
+                };
+            }
+            ValueKind::Ty(_) if path.is_empty() => return Ok(self.clone()),
+            ValueKind::Ty(_) => {
+                return Err(Error(ErrorKind::Deserialize(format!(
+                    "cannot access field `{}` of a type",
+                    path
+                ))))
+            }
+        };
+        for field in path.split('.').filter(|field| !field.is_empty()) {
+            val = match val {
+                SimpleValue::Record(kvs) => kvs.get(field),
+                _ => None,
+            }
+            .ok_or_else(|| {
+                Error(ErrorKind::Deserialize(format!(
+                    "no field `{}` found at path `{}`",
+                    field, path
+                )))
+            })?;
+            ty = field_type(ty, field);
+        }
+        Ok(Value {
+            kind: ValueKind::Val(val.clone(), ty.cloned()),
         })
     }
 
-    /// Converts a Value into a SimpleValue.
-    pub(crate) fn to_simple_value(&self) -> Option<SimpleValue> {
-        match &self.kind {
-            ValueKind::Val(val, _) => Some(val.clone()),
+    /// Returns the elements of this value if it is a list.
+    pub(crate) fn into_list_elements(self) -> Option<Vec<Value>> {
+        match self.kind {
+            ValueKind::Val(SimpleValue::List(xs), ty) => {
+                let ty = match ty {
+                    Some(SimpleType::List(t)) => Some(*t),
+                    _ => None,
+                };
+                Some(
+                    xs.into_iter()
+                        .map(|x| Value {
+                            kind: ValueKind::Val(x, ty.clone()),
+                        })
+                        .collect(),
+                )
+            }
             _ => None,
         }
     }
 
-    /// Converts a Value into a SimpleType.
-    pub(crate) fn to_simple_type(&self) -> Option<SimpleType> {
+    /// Returns the fields of this value if it is a record.
+    pub(crate) fn record_fields(&self) -> Option<BTreeMap<String, Value>> {
         match &self.kind {
-            ValueKind::Ty(ty) => Some(ty.clone()),
+            ValueKind::TyRecord(kvs) => Some(kvs.clone()),
+            ValueKind::Val(SimpleValue::Record(kvs), ty) => Some(
+                kvs.iter()
+                    .map(|(k, v)| {
+                        let ty = field_type(ty.as_ref(), k).cloned();
+                        (
+                            k.clone(),
+                            Value {
+                                kind: ValueKind::Val(v.clone(), ty),
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
             _ => None,
         }
     }
 
+    /// Drops the record fields that `ty` doesn't have, when the type of the value has extra
+    /// fields as checked by `SimpleType::has_extra_fields()`.
+    pub(crate) fn restrict_to(self, ty: &SimpleType) -> Value {
+        match self.kind {
+            ValueKind::Val(val, _) => Value {
+                kind: ValueKind::Val(val.restrict_to(ty), Some(ty.clone())),
+            },
+            kind => Value { kind },
+        }
+    }
+
+    /// Builds a record from its fields, keeping it a simple value when all the fields are.
+    fn from_fields(fields: BTreeMap<String, Value>) -> Value {
+        let mut vals = BTreeMap::new();
+        let mut tys = Some(HashMap::new());
+        for (k, v) in &fields {
+            match &v.kind {
+                ValueKind::Val(val, ty) => {
+                    vals.insert(k.clone(), val.clone());
+                    match (&mut tys, ty) {
+                        (Some(tys), Some(ty)) => {
+                            tys.insert(k.clone(), ty.clone());
+                        }
+                        _ => tys = None,
+                    }
+                }
+                _ => {
+                    return Value {
+                        kind: ValueKind::TyRecord(fields),
+                    }
+                }
+            }
+        }
+        Value {
+            kind: ValueKind::Val(
+                SimpleValue::Record(vals),
+                tys.map(SimpleType::Record),
+            ),
+        }
+    }
+
     /// Converts a value back to the corresponding AST expression.
     pub(crate) fn to_expr(&self) -> Expr {
         match &self.kind {
             ValueKind::Val(val, ty) => val.to_expr(ty.as_ref()).unwrap(),
             ValueKind::Ty(ty) => ty.to_expr(),
+            ValueKind::TyRecord(_) => Ctxt::with_new(|cx| {
+                self.to_hir().to_expr(cx, Default::default())
+            }),
+        }
+    }
+
+    fn to_hir<'cx>(&self) -> Hir<'cx> {
+        match &self.kind {
+            ValueKind::Val(val, ty) => val.to_hir(ty.as_ref()).unwrap(),
+            ValueKind::Ty(ty) => ty.to_hir(),
+            ValueKind::TyRecord(kvs) => Hir::new(
+                HirKind::Expr(ExprKind::RecordLit(
+                    kvs.iter()
+                        .map(|(k, v)| (k.as_str().into(), v.to_hir()))
+                        .collect(),
+                )),
+                Span::Artificial,
+            ),
+        }
+    }
+}
+
+/// Merges two records recursively, as is usual when layering configuration files.
+///
+/// Fields of `override_` replace the fields of `base` with the same name, except that when both
+/// are records they are merged recursively. Lists and other values are replaced as a whole. Unlike
+/// Dhall's `//`, which only merges the top level, and `/\`, which fails on conflicting fields, this
+/// never fails on nested fields. It errors if either argument is not a record.
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> serde_dhall::Result<()> {
+/// use serde_dhall::{deep_merge, Value};
+///
+/// let base: Value = serde_dhall::from_str(
+///     "{ server = { host = \"localhost\", port = 80 }, users = [ \"root\" ] }"
+/// ).parse()?;
+/// let local: Value = serde_dhall::from_str(
+///     "{ server = { port = 8080 }, users = [ \"me\" ] }"
+/// ).parse()?;
+///
+/// let merged = deep_merge(base, local)?;
+/// let expected: Value = serde_dhall::from_str(
+///     "{ server = { host = \"localhost\", port = 8080 }, users = [ \"me\" ] }"
+/// ).parse()?;
+/// assert_eq!(merged, expected);
+/// # Ok(())
+/// # }
+/// ```
+pub fn deep_merge(base: Value, override_: Value) -> Result<Value> {
+    match (base.record_fields(), override_.record_fields()) {
+        (Some(base), Some(override_)) => {
+            Ok(Value::from_fields(merge_fields(base, override_)))
         }
+        (None, _) => Err(Error(ErrorKind::Deserialize(format!(
+            "expected a record, found: {}",
+            base
+        )))),
+        (_, None) => Err(Error(ErrorKind::Deserialize(format!(
+            "expected a record, found: {}",
+            override_
+        )))),
+    }
+}
+
+fn merge_fields(
+    mut base: BTreeMap<String, Value>,
+    override_: BTreeMap<String, Value>,
+) -> BTreeMap<String, Value> {
+    for (k, v) in override_ {
+        let merged = match base.remove(&k) {
+            Some(old) => match (old.record_fields(), v.record_fields()) {
+                (Some(old), Some(new)) => {
+                    Value::from_fields(merge_fields(old, new))
+                }
+                _ => v,
+            },
+            None => v,
+        };
+        base.insert(k, merged);
+    }
+    base
+}
+
+/// A parsed Dhall value whose parts are only deserialized when requested.
+///
+/// The whole expression is still evaluated when parsing; what `LazyValue` avoids is building
+/// Rust values for the parts of it that are never read. This can be obtained with
+/// [`Deserializer::parse_lazy()`].
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> serde_dhall::Result<()> {
+/// let config = serde_dhall::from_str("{ server = { port = 8080, host = \"localhost\" } }")
+///     .parse_lazy()?;
+///
+/// let port: u16 = config.get("server.port")?;
+/// assert_eq!(port, 8080);
+/// assert!(config.get::<u16>("server.user").is_err());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Deserializer::parse_lazy()`]: crate::Deserializer::parse_lazy()
+#[derive(Debug, Clone)]
+pub struct LazyValue {
+    value: Value,
+    options: DeserializeOptions,
+}
+
+impl LazyValue {
+    pub(crate) fn new(value: Value, options: DeserializeOptions) -> Self {
+        LazyValue { value, options }
+    }
+
+    /// Deserializes the part of the value found at the given dotted path of record fields,
+    /// e.g. `"server.port"`. The empty path deserializes the whole value.
+    pub fn get<T: FromDhall>(&self, path: &str) -> Result<T> {
+        T::from_dhall_with_options(&self.value.get_path(path)?, self.options)
+    }
+}
+
+/// The type of the given field of a record of type `ty`.
+fn field_type<'a>(
+    ty: Option<&'a SimpleType>,
+    field: &str,
+) -> Option<&'a SimpleType> {
+    match ty {
+        Some(SimpleType::Record(kts)) => kts.get(field),
+        // A record can also come from a `Prelude.Map`.
+        Some(SimpleType::List(t)) => match t.as_ref() {
+            SimpleType::Record(kts) => kts.get("mapValue"),
+            _ => None,
+        },
+        _ => None,
     }
 }
 
@@ -261,6 +663,66 @@ impl Value {
 struct NotSimpleValue;
 
 impl SimpleValue {
+    /// Drops the record fields that `ty` doesn't have.
+    fn restrict_to(self, ty: &SimpleType) -> SimpleValue {
+        match (self, ty) {
+            (SimpleValue::Optional(Some(x)), SimpleType::Optional(t)) => {
+                SimpleValue::Optional(Some(Box::new(x.restrict_to(t))))
+            }
+            (SimpleValue::List(xs), SimpleType::List(t)) => SimpleValue::List(
+                xs.into_iter().map(|x| x.restrict_to(t)).collect(),
+            ),
+            (SimpleValue::Record(mut kvs), SimpleType::Record(kts)) => {
+                SimpleValue::Record(
+                    kts.iter()
+                        .filter_map(|(k, t)| {
+                            Some((k.clone(), kvs.remove(k)?.restrict_to(t)))
+                        })
+                        .collect(),
+                )
+            }
+            (SimpleValue::Union(k, Some(x)), SimpleType::Union(kts)) => {
+                let x = match kts.get(&k) {
+                    Some(Some(t)) => x.restrict_to(t),
+                    _ => *x,
+                };
+                SimpleValue::Union(k, Some(Box::new(x)))
+            }
+            (x, _) => x,
+        }
+    }
+
+    fn approx_eq(&self, other: &SimpleValue, epsilon: f64) -> bool {
+        use SimpleValue::*;
+        let approx_eq_boxed =
+            |a: &Option<Box<SimpleValue>>, b: &Option<Box<SimpleValue>>| match (
+                a, b,
+            ) {
+                (Some(a), Some(b)) => a.approx_eq(b, epsilon),
+                (None, None) => true,
+                _ => false,
+            };
+        match (self, other) {
+            (Num(NumKind::Double(a)), Num(NumKind::Double(b))) => {
+                let (a, b) = (f64::from(*a), f64::from(*b));
+                a == b || (a - b).abs() <= epsilon
+            }
+            (List(a), List(b)) => {
+                a.len() == b.len()
+                    && a.iter().zip(b).all(|(a, b)| a.approx_eq(b, epsilon))
+            }
+            (Record(a), Record(b)) => {
+                a.len() == b.len()
+                    && a.iter().zip(b).all(|((ka, va), (kb, vb))| {
+                        ka == kb && va.approx_eq(vb, epsilon)
+                    })
+            }
+            (Optional(a), Optional(b)) => approx_eq_boxed(a, b),
+            (Union(la, a), Union(lb, b)) => la == lb && approx_eq_boxed(a, b),
+            _ => self == other,
+        }
+    }
+
     fn from_nir(nir: &Nir) -> StdResult<Self, NotSimpleValue> {
         Ok(match nir.kind() {
             NirKind::Num(lit) => SimpleValue::Num(lit.clone()),
@@ -291,33 +753,30 @@ impl SimpleValue {
                         && kvs.contains_key("mapKey")
                         && kvs.contains_key("mapValue")
                     {
-                        let convert_entry = |x: &Nir| match x.kind() {
-                            NirKind::RecordLit(kvs) => {
-                                let k = match kvs.get("mapKey").unwrap().kind()
-                                {
-                                    NirKind::TextLit(t)
-                                        if t.as_text().is_some() =>
-                                    {
-                                        t.as_text().unwrap()
+                        // Only text keys make a map; otherwise this is a
+                        // plain list of records.
+                        let entries = xs
+                            .iter()
+                            .map(|x| match x.kind() {
+                                NirKind::RecordLit(kvs) => {
+                                    match kvs.get("mapKey").map(Nir::kind) {
+                                        Some(NirKind::TextLit(t)) => {
+                                            t.as_text().zip(kvs.get("mapValue"))
+                                        }
+                                        _ => None,
                                     }
-                                    // TODO
-                                    _ => panic!(
-                                        "Expected `mapKey` to be a text \
-                                         literal"
-                                    ),
-                                };
-                                let v = Self::from_nir(
-                                    kvs.get("mapValue").unwrap(),
-                                )?;
-                                Ok((k, v))
-                            }
-                            _ => unreachable!("Internal type error"),
-                        };
-                        return Ok(SimpleValue::Record(
-                            xs.iter()
-                                .map(convert_entry)
-                                .collect::<StdResult<_, _>>()?,
-                        ));
+                                }
+                                _ => None,
+                            })
+                            .collect::<Option<Vec<_>>>();
+                        if let Some(entries) = entries {
+                            return Ok(SimpleValue::Record(
+                                entries
+                                    .into_iter()
+                                    .map(|(k, v)| Ok((k, Self::from_nir(v)?)))
+                                    .collect::<StdResult<_, _>>()?,
+                            ));
+                        }
                     }
                 }
                 SimpleValue::List(
@@ -457,10 +916,154 @@ impl SimpleValue {
 }
 
 #[derive(Debug)]
-struct NotSimpleType;
+pub(crate) struct NotSimpleType;
 
 impl SimpleType {
-    fn from_nir(nir: &Nir) -> StdResult<Self, NotSimpleType> {
+    /// Whether a value of type `self` can be read where a value of type `other` is expected.
+    ///
+    /// Dhall itself has no subtyping, so this only accepts more than "these types are equal" in
+    /// the following ways, applied recursively:
+    ///
+    /// - a record type is a subtype of another if it has all of its fields, with subtypes of
+    ///   their types; extra fields are allowed, and fields of type `Optional T` may be missing;
+    /// - a union type is a subtype of another if all its alternatives are alternatives of the
+    ///   other, with subtypes of their types;
+    /// - `List T` and `Optional T` are subtypes of `List U` and `Optional U` when `T` is a subtype
+    ///   of `U`.
+    ///
+    /// In particular `T` is not a subtype of `Optional T`, and `Natural` is not a subtype of
+    /// `Integer`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::SimpleType;
+    ///
+    /// let schema: SimpleType =
+    ///     serde_dhall::from_str("{ name : Text, port : Optional Natural }").parse()?;
+    /// let config: SimpleType =
+    ///     serde_dhall::from_str("{ name : Text, debug : Bool }").parse()?;
+    ///
+    /// assert!(config.is_subtype_of(&schema));
+    /// assert!(!schema.is_subtype_of(&config));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_subtype_of(&self, other: &SimpleType) -> bool {
+        use SimpleType::*;
+        match (self, other) {
+            (Optional(t), Optional(u)) | (List(t), List(u)) => {
+                t.is_subtype_of(u)
+            }
+            (Record(kts), Record(kus)) => {
+                kus.iter().all(|(k, u)| match (kts.get(k), u) {
+                    (Some(t), _) => t.is_subtype_of(u),
+                    (None, Optional(_)) => true,
+                    (None, _) => false,
+                })
+            }
+            (Union(kts), Union(kus)) => {
+                kts.iter().all(|(k, t)| match (t, kus.get(k)) {
+                    (None, Some(None)) => true,
+                    (Some(t), Some(Some(u))) => t.is_subtype_of(u),
+                    _ => false,
+                })
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Whether `self` is `other` with more fields in some of its records, at any depth.
+    pub(crate) fn has_extra_fields(&self, other: &SimpleType) -> bool {
+        use SimpleType::*;
+        match (self, other) {
+            (Optional(t), Optional(u)) | (List(t), List(u)) => {
+                t.has_extra_fields(u)
+            }
+            (Record(kts), Record(kus)) => {
+                kus.iter().all(|(k, u)| match kts.get(k) {
+                    Some(t) => t.has_extra_fields(u),
+                    None => false,
+                })
+            }
+            (Union(kts), Union(kus)) => {
+                kts.len() == kus.len()
+                    && kts.iter().all(|(k, t)| match (t, kus.get(k)) {
+                        (None, Some(None)) => true,
+                        (Some(t), Some(Some(u))) => t.has_extra_fields(u),
+                        _ => false,
+                    })
+            }
+            _ => self == other,
+        }
+    }
+
+    /// The fields of a record type, sorted by name, or `None` if this is not a record type.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::SimpleType;
+    ///
+    /// let ty: SimpleType = serde_dhall::from_str("{ port : Natural, host : Text }").parse()?;
+    /// assert_eq!(
+    ///     ty.record_fields(),
+    ///     Some(vec![
+    ///         ("host".to_string(), SimpleType::Text),
+    ///         ("port".to_string(), SimpleType::Natural),
+    ///     ])
+    /// );
+    /// assert_eq!(SimpleType::Bool.record_fields(), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn record_fields(&self) -> Option<Vec<(String, SimpleType)>> {
+        match self {
+            SimpleType::Record(kts) => {
+                let mut fields: Vec<_> =
+                    kts.iter().map(|(k, t)| (k.clone(), t.clone())).collect();
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                Some(fields)
+            }
+            _ => None,
+        }
+    }
+
+    /// The alternatives of a union type, sorted by name, with the type of their payload if they
+    /// have one. Returns `None` if this is not a union type.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::SimpleType;
+    ///
+    /// let ty: SimpleType = serde_dhall::from_str("< Low | High : Natural >").parse()?;
+    /// assert_eq!(
+    ///     ty.union_variants(),
+    ///     Some(vec![
+    ///         ("High".to_string(), Some(SimpleType::Natural)),
+    ///         ("Low".to_string(), None),
+    ///     ])
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn union_variants(&self) -> Option<Vec<(String, Option<SimpleType>)>> {
+        match self {
+            SimpleType::Union(kts) => {
+                let mut variants: Vec<_> =
+                    kts.iter().map(|(k, t)| (k.clone(), t.clone())).collect();
+                variants.sort_by(|a, b| a.0.cmp(&b.0));
+                Some(variants)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_nir(nir: &Nir) -> StdResult<Self, NotSimpleType> {
         Ok(match nir.kind() {
             NirKind::BuiltinType(b) => match b {
                 Builtin::Bool => SimpleType::Bool,
@@ -533,24 +1136,16 @@ impl SimpleType {
 }
 
 impl crate::deserialize::Sealed for Value {}
-impl crate::deserialize::Sealed for SimpleType {}
 impl crate::serialize::Sealed for Value {}
 
 impl FromDhall for Value {
-    fn from_dhall(v: &Value) -> Result<Self> {
+    fn from_dhall_with_options(
+        v: &Value,
+        _opts: DeserializeOptions,
+    ) -> Result<Self> {
         Ok(v.clone())
     }
 }
-impl FromDhall for SimpleType {
-    fn from_dhall(v: &Value) -> Result<Self> {
-        v.to_simple_type().ok_or_else(|| {
-            Error(ErrorKind::Deserialize(format!(
-                "this cannot be deserialized into a simple type: {}",
-                v
-            )))
-        })
-    }
-}
 impl ToDhall for Value {
     fn to_dhall(&self, _ty: Option<&SimpleType>) -> Result<Value> {
         Ok(self.clone())
@@ -564,6 +1159,7 @@ impl PartialEq for ValueKind {
         match (self, other) {
             (Val(a, _), Val(b, _)) => a == b,
             (Ty(a), Ty(b)) => a == b,
+            (TyRecord(a), TyRecord(b)) => a == b,
             _ => false,
         }
     }
@@ -0,0 +1,48 @@
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Config {
+    #[serde(rename = "fooBar")]
+    pub foo_bar: bool,
+    #[serde(rename = "foo_bar")]
+    pub foo_bar2: bool,
+    #[serde(rename = "log-level")]
+    pub log_level: ConfigLogLevel,
+    pub mode: ConfigMode,
+    pub name: String,
+    #[serde(rename = "offsetX")]
+    pub offset_x: i64,
+    pub ratio: f64,
+    pub server: ConfigServer,
+    pub tags: Vec<ConfigTags>,
+    pub r#type: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub enum ConfigLogLevel {
+    Custom(ConfigLogLevelCustom),
+    Debug,
+    Info,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct ConfigLogLevelCustom {
+    pub level: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub enum ConfigMode {
+    A,
+    #[serde(rename = "a")]
+    A2,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct ConfigServer {
+    pub host: String,
+    pub port: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct ConfigTags {
+    pub key: String,
+    pub value: String,
+}
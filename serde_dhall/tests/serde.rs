@@ -1,7 +1,8 @@
 mod serde {
     use serde::{Deserialize, Serialize};
     use serde_dhall::{
-        from_str, serialize, FromDhall, StaticType, ToDhall, Value,
+        from_str, serialize, FromDhall, NumKind, SimpleType, SimpleValue,
+        StaticType, ToDhall, Value,
     };
     use std::collections;
 
@@ -41,6 +42,25 @@ mod serde {
         assert_de(s, x.clone());
         assert_ser(s, x);
     }
+    /// Creates a fresh directory for the test `name`, unique to this process, holding the given
+    /// files (parent directories are created as needed).
+    fn fixture_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        use std::fs;
+
+        let dir = std::env::temp_dir().join(format!(
+            "serde_dhall_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
 
     #[test]
     fn numbers() {
@@ -58,6 +78,110 @@ mod serde {
         assert_serde("1.0", 1.0f32);
     }
 
+    #[test]
+    fn special_doubles() {
+        assert_serde("Infinity", f64::INFINITY);
+        assert_serde("-Infinity", f64::NEG_INFINITY);
+        assert!(from_str("NaN").parse::<f64>().unwrap().is_nan());
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        struct Bounds {
+            min: f64,
+            max: f64,
+        }
+        assert_de(
+            "{ min = -Infinity, max = Infinity }",
+            Bounds {
+                min: f64::NEG_INFINITY,
+                max: f64::INFINITY,
+            },
+        );
+
+        assert_de("Infinity", f32::INFINITY);
+        assert_de("-Infinity", f32::NEG_INFINITY);
+        assert!(from_str("NaN").parse::<f32>().unwrap().is_nan());
+        assert_de("-3.4e38", -3.4e38f32);
+        assert_eq!(
+            from_str("1e39").parse::<f32>().map_err(|e| e.to_string()),
+            Err("double 1e39 is out of range for f32".to_string())
+        );
+    }
+
+    #[test]
+    fn integer_out_of_range() {
+        fn parse<T: serde_dhall::FromDhall>(s: &str) -> Result<T, String> {
+            from_str(s).parse().map_err(|e| e.to_string())
+        }
+
+        assert_eq!(parse::<i32>("-2147483648"), Ok(i32::MIN));
+        assert_eq!(
+            parse::<i32>("+3000000000"),
+            Err("integer +3000000000 is out of range for i32".to_string())
+        );
+        assert_eq!(
+            parse::<i8>("-129"),
+            Err("integer -129 is out of range for i8".to_string())
+        );
+        assert_eq!(
+            parse::<i64>("9223372036854775808"),
+            Err("integer 9223372036854775808 is out of range for i64"
+                .to_string())
+        );
+        assert_eq!(
+            parse::<Vec<i16>>("[ +1, +40000 ]"),
+            Err("integer +40000 is out of range for i16 at `[1]`".to_string())
+        );
+        assert_eq!(parse::<u8>("255"), Ok(255));
+        assert_eq!(
+            parse::<u8>("256"),
+            Err("integer 256 is out of range for u8".to_string())
+        );
+        assert_eq!(parse::<u64>("+1"), Ok(1));
+        assert_eq!(
+            parse::<u32>("-1"),
+            Err("integer -1 is out of range for u32".to_string())
+        );
+    }
+
+    #[test]
+    fn integers_128() {
+        fn parse<T: serde_dhall::FromDhall>(s: &str) -> Result<T, String> {
+            from_str(s).parse().map_err(|e| e.to_string())
+        }
+        // `Natural`s and `Integer`s are 64-bit, so they always fit.
+        assert_eq!(parse::<u128>("18446744073709551615"), Ok(u64::MAX.into()));
+        assert_eq!(parse::<i128>("+9223372036854775807"), Ok(i64::MAX.into()));
+        assert_eq!(parse::<i128>("-9223372036854775808"), Ok(i64::MIN.into()));
+        assert_eq!(parse::<i128>("18446744073709551615"), Ok(u64::MAX.into()));
+        assert_eq!(parse::<Vec<u128>>("[ 1, 2 ]"), Ok(vec![1, 2]));
+
+        assert_eq!(
+            parse::<u128>("-1"),
+            Err("integer -1 is out of range for u128".to_string())
+        );
+        assert_eq!(parse::<u128>("+1"), Ok(1));
+        // Larger literals can't be represented.
+        assert!(parse::<u128>("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn natural_comparisons() {
+        fn parse(s: &str) -> bool {
+            from_str(s).parse().unwrap()
+        }
+
+        let lte = "let lessThanEqual = \\(a: Natural) -> \\(b: Natural) -> \
+                   Natural/isZero (Natural/subtract b a) in ";
+        assert!(parse(&format!("{}lessThanEqual 3 5", lte)));
+        assert!(parse(&format!("{}lessThanEqual 5 5", lte)));
+        assert!(!parse(&format!("{}lessThanEqual 5 3", lte)));
+        assert!(parse("Natural/isZero (Natural/subtract 2 1)"));
+
+        assert_de("Natural/toInteger 3", 3i64);
+        assert!(from_str("Natural/toInteger 18446744073709551615")
+            .parse::<i64>()
+            .is_err());
+    }
+
     #[test]
     fn text() {
         assert_serde(r#""foo""#, "foo".to_owned());
@@ -87,6 +211,18 @@ mod serde {
         );
     }
 
+    #[test]
+    fn nested_optional() {
+        assert_serde("None (Optional Natural)", None::<Option<u64>>);
+        assert_serde("Some (None Natural)", Some(None::<u64>));
+        assert_serde("Some (Some 1)", Some(Some(1u64)));
+        assert_de("None Natural : Optional Natural", None::<u64>);
+        assert_de(
+            "Some (None Natural) : Optional (Optional Natural)",
+            Some(None::<u64>),
+        );
+    }
+
     #[test]
     fn tuple() {
         assert_serde::<()>(r#"{=}"#, ());
@@ -122,6 +258,8 @@ mod serde {
             y: i64,
         }
         assert_serde::<Baz>("{ x = 1, y = -2 }", Baz { x: 1, y: -2 });
+        // Punned fields
+        assert_de("let x = 1 let y = -2 in { x, y }", Baz { x: 1, y: -2 });
     }
 
     #[test]
@@ -150,6 +288,485 @@ mod serde {
             .is_err());
     }
 
+    #[test]
+    fn list_of_enums() {
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, StaticType)]
+        enum Item {
+            A(u64),
+            B(String),
+            C,
+            D { x: bool },
+        }
+
+        let items = vec![
+            Item::A(1),
+            Item::B("b".to_string()),
+            Item::A(2),
+            Item::C,
+            Item::D { x: true },
+            Item::C,
+        ];
+        assert_de(
+            "let T = < A: Natural | B: Text | C | D: { x: Bool } > \
+             in [ T.A 1, T.B \"b\", T.A 2, T.C, T.D { x = True }, T.C ]",
+            items.clone(),
+        );
+        // Without a type annotation, too.
+        assert_eq!(
+            from_str(
+                "let T = < A: Natural | B: Text | C | D: { x: Bool } > \
+                 in [ T.A 1, T.B \"b\", T.A 2, T.C, T.D { x = True }, T.C ]"
+            )
+            .parse::<Vec<Item>>()
+            .ok(),
+            Some(items)
+        );
+    }
+
+    #[test]
+    fn ignored_any() {
+        use serde::de::IgnoredAny;
+        use serde_dhall::SimpleType;
+
+        let config = r#"
+            let Level = < Low | High : Natural >
+            in { name = "foo", levels = [ Level.Low, Level.High 3 ], ratio = -1.5,
+                 offset = +2, extra = None { x : Bool }, pair = { _1 = True, _2 = "b" } }
+        "#;
+        let ty: SimpleType = from_str(
+            "{ name : Text, levels : List < Low | High : Natural >, ratio : Double, \
+             offset : Integer, extra : Optional { x : Bool }, pair : { _1 : Bool, _2 : Text } }",
+        )
+        .parse()
+        .unwrap();
+
+        assert!(from_str(config).parse::<IgnoredAny>().is_ok());
+        assert!(from_str(config)
+            .type_annotation(&ty)
+            .parse::<IgnoredAny>()
+            .is_ok());
+        // Errors are still reported.
+        assert!(from_str("{ x = 1 + True }").parse::<IgnoredAny>().is_err());
+        assert!(from_str(r#"{ name = "foo" }"#)
+            .type_annotation(&ty)
+            .parse::<IgnoredAny>()
+            .is_err());
+    }
+
+    #[test]
+    fn result() {
+        type Res = Result<u64, String>;
+        let ty = "< Err: Text | Ok: Natural >";
+
+        assert_serde(&format!("{}.Ok 1", ty), Res::Ok(1));
+        assert_serde(&format!("{}.Err \"oops\"", ty), Res::Err("oops".into()));
+        assert_eq!(
+            from_str(&format!("[ ({0}).Ok 1, ({0}).Err \"no\" ]", ty))
+                .parse::<Vec<Res>>()
+                .ok(),
+            Some(vec![Ok(1), Err("no".to_string())])
+        );
+        // Other names are not recognized.
+        assert!(from_str("< Success : Natural | Failure : Text >.Success 1")
+            .parse::<Res>()
+            .is_err());
+    }
+
+    #[test]
+    fn maps_with_non_text_keys() {
+        use collections::{BTreeMap, HashMap};
+
+        let naturals: HashMap<u64, String> = from_str(
+            r#"[ { mapKey = 1, mapValue = "one" }, { mapKey = 2, mapValue = "two" } ]"#,
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(naturals.len(), 2);
+        assert_eq!(naturals[&1], "one");
+        assert_eq!(naturals[&2], "two");
+
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+        enum Level {
+            Low,
+            High,
+        }
+        let levels: BTreeMap<Level, Vec<bool>> = from_str(
+            "let Level = < Low | High > \
+             in [ { mapKey = Level.High, mapValue = [ True ] }, \
+                  { mapKey = Level.Low, mapValue = [] : List Bool } ]",
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(
+            levels.into_iter().collect::<Vec<_>>(),
+            vec![(Level::Low, vec![]), (Level::High, vec![true])]
+        );
+
+        let empty: HashMap<u64, bool> =
+            from_str("[] : List { mapKey : Natural, mapValue : Bool }")
+                .parse()
+                .unwrap();
+        assert!(empty.is_empty());
+
+        // Such a list is still a list when a list is expected.
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Entry {
+            #[serde(rename = "mapKey")]
+            key: u64,
+            #[serde(rename = "mapValue")]
+            value: bool,
+        }
+        assert_eq!(
+            from_str("[ { mapKey = 1, mapValue = True } ]")
+                .parse::<Vec<Entry>>()
+                .ok(),
+            Some(vec![Entry {
+                key: 1,
+                value: true
+            }])
+        );
+    }
+
+    #[test]
+    fn sets() {
+        let set = |xs: &[u64]| xs.iter().copied().collect();
+        assert_de::<collections::HashSet<u64>>("[1, 2, 3]", set(&[1, 2, 3]));
+        assert_de::<collections::HashSet<u64>>("[] : List Natural", set(&[]));
+        // Duplicate elements are dropped, like when collecting an iterator.
+        assert_de::<collections::HashSet<u64>>("[1, 2, 1, 1]", set(&[1, 2]));
+        assert_serde::<collections::BTreeSet<u64>>(
+            "[1, 2, 3]",
+            [3, 1, 2].iter().copied().collect(),
+        );
+        assert_de::<collections::BTreeSet<String>>(
+            r#"["b", "a", "b"]"#,
+            vec!["a".to_owned(), "b".to_owned()].into_iter().collect(),
+        );
+    }
+
+    #[test]
+    fn hash_maps_serialize_sorted() {
+        use collections::{BTreeMap, HashMap};
+
+        let keys: Vec<String> = (0..50).map(|i| format!("key{}", i)).collect();
+        // Each `HashMap` has its own random seed, so they iterate in different orders.
+        let forward: HashMap<&str, usize> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| (k.as_str(), i))
+            .collect();
+        let backward: HashMap<&str, usize> = keys
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, k)| (k.as_str(), i))
+            .collect();
+        let sorted: BTreeMap<&str, usize> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| (k.as_str(), i))
+            .collect();
+
+        let forward = serialize(&forward).to_string().unwrap();
+        assert_eq!(forward, serialize(&backward).to_string().unwrap());
+        assert_eq!(forward, serialize(&sorted).to_string().unwrap());
+        assert!(forward.starts_with("{ key0 = 0, key1 = 1, key10 = 10,"));
+    }
+
+    #[test]
+    fn unit_from_empty_record() {
+        #[derive(
+            Debug, Clone, PartialEq, Deserialize, Serialize, StaticType,
+        )]
+        struct Marker;
+
+        #[derive(
+            Debug, Clone, PartialEq, Deserialize, Serialize, StaticType,
+        )]
+        enum Shape {
+            Empty(()),
+            Dot(Marker),
+            Size(u64),
+        }
+
+        assert_serde("{=}", ());
+        assert_serde("{=}", Marker);
+        assert_serde(
+            "< Dot: {} | Empty: {} | Size: Natural >.Dot {=}",
+            Shape::Dot(Marker),
+        );
+        assert_serde(
+            "< Dot: {} | Empty: {} | Size: Natural >.Empty {=}",
+            Shape::Empty(()),
+        );
+
+        assert!(from_str("{ x = 1 }").parse::<()>().is_err());
+        assert!(from_str("{ x = 1 }").parse::<Marker>().is_err());
+        assert!(from_str("< Dot : { x : Natural } >.Dot { x = 1 }")
+            .parse::<Shape>()
+            .is_err());
+    }
+
+    #[test]
+    fn parse_seed() {
+        use serde::de::{DeserializeSeed, MapAccess, SeqAccess, Visitor};
+        use std::cell::Cell;
+        use std::fmt;
+
+        /// Counts the nodes of the value.
+        #[derive(Clone, Copy)]
+        struct Counter<'a>(&'a Cell<usize>);
+
+        impl<'de> DeserializeSeed<'de> for Counter<'_> {
+            type Value = ();
+            fn deserialize<D>(self, d: D) -> Result<(), D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                d.deserialize_any(self)
+            }
+        }
+
+        impl<'de> Visitor<'de> for Counter<'_> {
+            type Value = ();
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("anything")
+            }
+            fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+                self.0.set(self.0.get() + 1);
+                Ok(())
+            }
+            fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+                self.0.set(self.0.get() + 1);
+                Ok(())
+            }
+            fn visit_str<E>(self, _: &str) -> Result<(), E> {
+                self.0.set(self.0.get() + 1);
+                Ok(())
+            }
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<(), A::Error> {
+                self.0.set(self.0.get() + 1);
+                while seq.next_element_seed(self)?.is_some() {}
+                Ok(())
+            }
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<(), A::Error> {
+                self.0.set(self.0.get() + 1);
+                while map.next_key::<String>()?.is_some() {
+                    map.next_value_seed(self)?;
+                }
+                Ok(())
+            }
+        }
+
+        let count = Cell::new(0);
+        from_str(r#"{ a = [ 1, 2 ], b = { c = True, d = "x" } }"#)
+            .parse_seed(Counter(&count))
+            .unwrap();
+        assert_eq!(count.get(), 7);
+
+        // The value is typechecked against the annotation before the seed runs.
+        let count = Cell::new(0);
+        let ty = from_str("List Natural").parse::<SimpleType>().unwrap();
+        assert!(from_str("[ True ]")
+            .type_annotation(&ty)
+            .parse_seed(Counter(&count))
+            .is_err());
+        assert_eq!(count.get(), 0);
+    }
+
+    #[test]
+    fn parse_list_iter() {
+        let src = format!(
+            "[ {} ]",
+            (0..10_000u64)
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let mut count = 0;
+        let mut total = 0;
+        for x in from_str(&src).parse_list_iter::<u64>().unwrap() {
+            count += 1;
+            total += x.unwrap();
+        }
+        assert_eq!(count, 10_000);
+        assert_eq!(total, 9_999 * 10_000 / 2);
+
+        let results: Vec<_> = from_str("[ 1, 300, 2 ]")
+            .parse_list_iter::<u8>()
+            .unwrap()
+            .collect();
+        assert_eq!(results[0].as_ref().unwrap(), &1);
+        assert_eq!(results[1].as_ref().unwrap_err().path(), Some("[1]"));
+        assert_eq!(results[2].as_ref().unwrap(), &2);
+
+        assert!(from_str("{ x = 1 }").parse_list_iter::<u64>().is_err());
+
+        let xs: Vec<String> = from_str("[ \"a\", \"b\" ]")
+            .static_type_annotation()
+            .parse_list_iter()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(xs, vec!["a".to_owned(), "b".to_owned()]);
+        assert!(from_str("[ 1, 2 ]")
+            .static_type_annotation()
+            .parse_list_iter::<String>()
+            .is_err());
+    }
+
+    #[test]
+    fn smart_pointers() {
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        #[derive(
+            Debug, Clone, PartialEq, Deserialize, Serialize, StaticType,
+        )]
+        struct Point {
+            x: u64,
+            y: u64,
+        }
+        let point = || Point { x: 1, y: 2 };
+        assert_serde("{ x = 1, y = 2 }", Box::new(point()));
+        assert_serde("{ x = 1, y = 2 }", Rc::new(point()));
+        assert_serde("{ x = 1, y = 2 }", Arc::new(point()));
+        assert_serde::<Vec<Arc<Point>>>(
+            "[] : List { x : Natural, y : Natural }",
+            vec![],
+        );
+
+        let config: Arc<Point> = from_str("{ x = 1, y = 1 + 1 }")
+            .static_type_annotation()
+            .parse()
+            .unwrap();
+        assert_eq!(*config, point());
+    }
+
+    #[test]
+    fn owned_strings() {
+        use std::borrow::Cow;
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct User {
+            name: Cow<'static, str>,
+            email: Box<str>,
+        }
+        let user: User =
+            from_str(r#"let domain = "example.com" in { name = "ann", email = "ann@${domain}" }"#)
+                .parse()
+                .unwrap();
+        assert_eq!(
+            user,
+            User {
+                name: Cow::Borrowed("ann"),
+                email: "ann@example.com".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn flatten_catch_all() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Service {
+            name: String,
+            port: u16,
+            #[serde(flatten)]
+            extra: collections::BTreeMap<String, SimpleValue>,
+        }
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Limits {
+            name: String,
+            #[serde(flatten)]
+            limits: collections::HashMap<String, u64>,
+        }
+
+        let service = from_str(
+            r#"{ name = "web", port = 80, replicas = 3, tags = [ "a" ], tls = None Bool }"#,
+        )
+        .parse::<Service>()
+        .unwrap();
+        assert_eq!(service.name, "web");
+        assert_eq!(service.port, 80);
+        let mut extra = collections::BTreeMap::new();
+        extra.insert(
+            "replicas".to_owned(),
+            SimpleValue::Num(NumKind::Natural(3)),
+        );
+        extra.insert(
+            "tags".to_owned(),
+            SimpleValue::List(vec![SimpleValue::Text("a".to_owned())]),
+        );
+        extra.insert("tls".to_owned(), SimpleValue::Optional(None));
+        assert_eq!(service.extra, extra);
+
+        let limits = from_str(r#"{ cpu = 2, memory = 512, name = "db" }"#)
+            .parse::<Limits>()
+            .unwrap();
+        assert_eq!(limits.name, "db");
+        assert_eq!(limits.limits.len(), 2);
+        assert_eq!(limits.limits["memory"], 512);
+    }
+
+    #[test]
+    fn chars() {
+        assert_serde(r#""a""#, 'a');
+        assert_serde(r#""é""#, 'é');
+        assert_serde(r#""🦀""#, '🦀');
+        assert_de(r#""\u00E9""#, 'é');
+
+        let parse = |s: &str| {
+            from_str(s)
+                .static_type_annotation()
+                .parse::<char>()
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            parse(r#""ab""#),
+            Err(r#"expected a text of exactly one character, found "ab""#
+                .to_owned())
+        );
+        assert!(parse(r#""""#).is_err());
+        // An accent written as a combining character is a separate scalar value.
+        assert!(parse(r#""e\u0301""#).is_err());
+        assert!(parse("1").is_err());
+    }
+
+    #[test]
+    fn union_as_string() {
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        enum Color {
+            Red,
+            Green,
+            Blue,
+        }
+
+        let colors = "< Red | Green | Blue >";
+        assert_de(&format!("{}.Green", colors), Color::Green);
+        assert_eq!(
+            from_str(&format!("{}.Green", colors))
+                .parse::<String>()
+                .ok(),
+            Some("Green".to_string())
+        );
+        assert_eq!(
+            from_str(&format!("[ {0}.Red, {0}.Blue ]", colors))
+                .parse::<Vec<String>>()
+                .ok(),
+            Some(vec!["Red".to_string(), "Blue".to_string()])
+        );
+        // Alternatives with a payload are not strings.
+        assert!(from_str("< Red : Natural | Green >.Red 1")
+            .parse::<String>()
+            .is_err());
+    }
+
     #[test]
     fn with_builtin_type() {
         #[derive(Debug, Deserialize, StaticType, Eq, PartialEq)]
@@ -253,6 +870,17 @@ mod serde {
             parse::<HashMap<String, u64>>("toMap { x = 1, y = 2 }"),
             expected_map
         );
+        assert_eq!(
+            parse::<HashMap<String, u64>>(
+                "toMap {=} : List { mapKey : Text, mapValue : Natural }"
+            ),
+            HashMap::new()
+        );
+        let err = from_str("toMap {=}")
+            .parse::<HashMap<String, u64>>()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("requires a type annotation"), "{}", err);
 
         let mut expected_map = HashMap::new();
         expected_map.insert("if".to_string(), 1);
@@ -285,6 +913,40 @@ mod serde {
         assert!(from_str("List/length [True, 42]").parse::<bool>().is_err());
     }
 
+    #[test]
+    fn collect_errors() {
+        use serde_dhall::SimpleType;
+        use std::collections::HashMap;
+
+        let data = r#"{ a = 1 + True, b = [ 1, "x" ++ 2 ], c = 3 }"#;
+        let err = from_str(data).parse::<Value>().unwrap_err();
+        assert_eq!(err.errors().count(), 1);
+        let err = from_str(data)
+            .collect_errors(true)
+            .parse::<Value>()
+            .unwrap_err();
+        assert_eq!(err.errors().count(), 2);
+
+        let ty: SimpleType =
+            from_str("{ x: Natural, y: List Natural }").parse().unwrap();
+        let err = from_str(r#"{ x = True, y = [ 1, +2, "foo" ] }"#)
+            .type_annotation(&ty)
+            .collect_errors(true)
+            .parse::<HashMap<String, serde_dhall::SimpleValue>>()
+            .unwrap_err();
+        assert_eq!(err.errors().count(), 3);
+
+        assert_eq!(
+            from_str("{ x = 1, y = [ 2 ] }")
+                .type_annotation(&ty)
+                .collect_errors(true)
+                .parse::<HashMap<String, serde_dhall::SimpleValue>>()
+                .map(|m| m.len())
+                .map_err(|e| e.to_string()),
+            Ok(2)
+        );
+    }
+
     #[test]
     fn test_file() {
         assert_eq!(
@@ -325,6 +987,142 @@ mod serde {
         );
     }
 
+    #[test]
+    fn prelude_text_concat_sep() {
+        let concat_sep = "./tests/prelude/Text/concatSep.dhall";
+        assert_de(
+            &format!(r#"{} ", " [ "a", "b", "c" ]"#, concat_sep),
+            "a, b, c".to_string(),
+        );
+        assert_de(
+            &format!(r#"{} ", " ([] : List Text)"#, concat_sep),
+            "".to_string(),
+        );
+        // The elements can come from another computation over a list.
+        assert_de(
+            &format!(
+                r#"{} "/" (List/reverse Text (List/build Text (\(l : Type) -> \(cons : Text -> l -> l) -> \(nil : l) -> cons "usr" (cons (Natural/show 42) nil))))"#,
+                concat_sep
+            ),
+            "42/usr".to_string(),
+        );
+    }
+
+    #[test]
+    fn newtypes() {
+        use std::path::PathBuf;
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct License(String);
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Port(u16);
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Paths(Vec<PathBuf>);
+
+        let file = "./tests/prelude/Text/concatSep.dhall";
+        let license: License =
+            from_str(&format!("{} as Text", file)).parse().unwrap();
+        assert_eq!(license, License(std::fs::read_to_string(file).unwrap()));
+        assert_eq!(
+            from_str("\"MIT\"").parse::<License>().ok(),
+            Some(License("MIT".to_string()))
+        );
+        assert_eq!(from_str("8080").parse::<Port>().ok(), Some(Port(8080)));
+        assert_eq!(
+            from_str(&format!("[ \"/etc\", \"{}\" ]", file))
+                .parse::<Paths>()
+                .ok(),
+            Some(Paths(vec![PathBuf::from("/etc"), PathBuf::from(file)]))
+        );
+
+        // The derived type, the deserializer and the serializer agree.
+        #[derive(
+            Debug, Clone, PartialEq, Serialize, Deserialize, StaticType,
+        )]
+        struct Limit(u16);
+        #[derive(
+            Debug, Clone, PartialEq, Serialize, Deserialize, StaticType,
+        )]
+        struct Limits {
+            mem: Limit,
+            cpus: Vec<Limit>,
+        }
+        assert_serde("80", Limit(80));
+        assert_serde(
+            "{ cpus = [1, 2], mem = 512 }",
+            Limits {
+                mem: Limit(512),
+                cpus: vec![Limit(1), Limit(2)],
+            },
+        );
+        assert!(from_str("{ _1 = 80 }")
+            .static_type_annotation()
+            .parse::<Limit>()
+            .is_err());
+    }
+
+    #[test]
+    fn newtype_over_union() {
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        enum Color {
+            Red,
+            Green,
+        }
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        struct W(Color);
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        enum Shape {
+            Square(u64),
+            Circle { radius: u64 },
+        }
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        struct S(Shape);
+
+        assert_eq!(W::static_type(), Color::static_type());
+        assert_eq!(
+            from_str("< Red | Green >.Green")
+                .static_type_annotation()
+                .parse::<W>()
+                .ok(),
+            Some(W(Color::Green))
+        );
+
+        assert_eq!(
+            from_str("< Red | Green >.Red").parse::<W>().ok(),
+            Some(W(Color::Red))
+        );
+        assert_eq!(
+            from_str("let C = < Red | Green > in [ C.Green, C.Red ]")
+                .parse::<Vec<W>>()
+                .ok(),
+            Some(vec![W(Color::Green), W(Color::Red)])
+        );
+        let shape = "< Square : Natural | Circle : { radius : Natural } >";
+        assert_eq!(
+            from_str(&format!("{}.Square 2", shape)).parse::<S>().ok(),
+            Some(S(Shape::Square(2)))
+        );
+        assert_eq!(
+            from_str(&format!("{}.Circle {{ radius = 3 }}", shape))
+                .parse::<S>()
+                .ok(),
+            Some(S(Shape::Circle { radius: 3 }))
+        );
+        assert!(from_str("< Red | Blue >.Blue").parse::<W>().is_err());
+    }
+
+    #[test]
+    fn prelude_natural_enumerate() {
+        let enumerate = "./tests/prelude/Natural/enumerate.dhall";
+        assert_de(&format!("{} 5", enumerate), vec![0u64, 1, 2, 3, 4]);
+        assert_de(&format!("{} 0", enumerate), Vec::<u64>::new());
+        // The result can be combined with other lists.
+        assert_de(
+            &format!("{} 2 # [ 7 ] # {} 1", enumerate, enumerate),
+            vec![0u64, 1, 7, 0],
+        );
+    }
+
     #[test]
     #[ignore] // Way too slow
     fn test_prelude() {
@@ -339,6 +1137,1510 @@ mod serde {
         );
     }
 
+    #[test]
+    fn assert_exact_type() {
+        use serde_dhall::SimpleType;
+
+        let ty: SimpleType = from_str("{ x: Natural, y: Optional Text }")
+            .parse()
+            .unwrap();
+        let parse = |s: &str| {
+            from_str(s)
+                .assert_exact_type(&ty)
+                .parse::<collections::HashMap<String, serde_dhall::SimpleValue>>()
+                .map_err(|e| e.to_string())
+        };
+
+        assert!(parse("{ x = 1, y = Some \"foo\" }").is_ok());
+        assert!(parse("{ x = 1 }").is_err());
+        let err = parse("{ x = 1, y = None Text, z = True }").unwrap_err();
+        assert!(err.contains("z : Bool"));
+    }
+
+    #[test]
+    fn parse_lazy() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Server {
+            port: u16,
+        }
+
+        let data = from_str(
+            "{ server = { port = 80 }, \
+               env = toMap { HOME = \"/root\" }, \
+               empty = [] : List Natural }",
+        )
+        .parse_lazy()
+        .unwrap();
+        assert_eq!(data.get::<Server>("server").unwrap(), Server { port: 80 });
+        assert_eq!(data.get::<u16>("server.port").unwrap(), 80);
+        assert_eq!(data.get::<String>("env.HOME").unwrap(), "/root");
+        assert_eq!(data.get::<Vec<u64>>("empty").unwrap(), Vec::<u64>::new());
+        assert!(data.get::<u16>("server.port.x").is_err());
+        assert!(data.get::<u16>("client").is_err());
+    }
+
+    #[test]
+    fn deep_merge() {
+        use serde_dhall::deep_merge;
+
+        let value = |s: &str| from_str(s).parse::<Value>().unwrap();
+        let merge = |a: &str, b: &str| {
+            deep_merge(value(a), value(b)).map_err(|e| e.to_string())
+        };
+
+        assert_eq!(
+            merge(
+                "{ a = { b = 1, c = [ 1 ] }, d = True }",
+                "{ a = { c = [ 2 ] } }"
+            ),
+            Ok(value("{ a = { b = 1, c = [ 2 ] }, d = True }"))
+        );
+        // Fields that are not records on both sides are replaced.
+        assert_eq!(
+            merge("{ a = 1, b = { c = 2 } }", "{ a = { x = 1 }, b = 3 }"),
+            Ok(value("{ a = { x = 1 }, b = 3 }"))
+        );
+        // Records containing types are merged too.
+        assert_eq!(
+            merge(
+                "{ T = Natural, x = { y = 1 } }",
+                "{ T = Bool, x = { z = 2 } }"
+            ),
+            Ok(value("{ T = Bool, x = { y = 1, z = 2 } }"))
+        );
+        assert_eq!(
+            merge("{ a = 1 }", "[ 1 ]"),
+            Err("expected a record, found: [1]".to_string())
+        );
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Config {
+            port: u64,
+            hosts: Vec<String>,
+        }
+        let merged =
+            merge(r#"{ port = 80, hosts = [ "a" ] }"#, "{ port = 8080 }")
+                .unwrap();
+        assert_eq!(
+            merged.deserialize::<Config>().unwrap(),
+            Config {
+                port: 8080,
+                hosts: vec!["a".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn value_approx_eq() {
+        let value = |s: &str| from_str(s).parse::<Value>().unwrap();
+
+        let a = value(
+            "{ x = [0.30000000000000004], y = Some 1.0, z = < A : Double >.A 2.0 }",
+        );
+        let b = value(
+            "{ x = [0.3], y = Some 1.0000001, z = < A : Double >.A 2.0 }",
+        );
+        assert!(a.approx_eq(&b, 1e-6));
+        assert!(!a.approx_eq(&b, 1e-9));
+        assert!(!a.approx_eq(&value("{ x = [0.3] }"), 1.0));
+        assert!(value("1").approx_eq(&value("1"), 0.0));
+        assert!(!value("1").approx_eq(&value("2"), 10.0));
+        assert!(value("{ T = Natural, x = 1.0 }")
+            .approx_eq(&value("{ T = Natural, x = 1.01 }"), 0.1));
+    }
+
+    #[test]
+    fn value_type_of() {
+        let value = |s: &str| from_str(s).parse::<Value>().unwrap();
+        let ty = |s: &str| from_str(s).parse::<SimpleType>().unwrap();
+
+        assert_eq!(value("1 + 1").type_of(), Some(ty("Natural")));
+        assert_eq!(
+            value("{ x = None Text, y = < A | B : Bool >.A }").type_of(),
+            Some(ty("{ x : Optional Text, y : < A | B : Bool > }"))
+        );
+        assert_eq!(value("Bool").type_of(), None);
+        assert_eq!(value("{ T = Bool, x = 1 }").type_of(), None);
+    }
+
+    #[test]
+    fn value_deserialize() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        enum Shape {
+            Circle { radius: u64 },
+            Square { side: u64 },
+        }
+
+        let value: Value = from_str(
+            "< Circle : { radius : Natural } | Square : { side : Natural } >.Square { side = 3 }",
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(
+            value.deserialize::<Shape>().map_err(|e| e.to_string()),
+            Ok(Shape::Square { side: 3 })
+        );
+        // The same value can be converted several times.
+        assert_eq!(value.deserialize::<Value>().unwrap(), value);
+        assert!(value.deserialize::<u64>().is_err());
+    }
+
+    #[test]
+    fn parse_with_timing() {
+        use std::time::Duration;
+
+        // Make fetching imports slow enough to stand out.
+        let delay = Duration::from_millis(20);
+        let (data, timing) = from_str("env:A + env:B")
+            .env_vars(vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "2".to_string()),
+            ])
+            .on_import(|_, _| std::thread::sleep(delay))
+            .parse_with_timing::<u64>()
+            .unwrap();
+        assert_eq!(data, 3);
+        assert!(timing.resolve >= 2 * delay);
+        assert_eq!(
+            timing.total(),
+            timing.parse
+                + timing.resolve
+                + timing.typecheck
+                + timing.normalize
+                + timing.deserialize
+        );
+        assert!(from_str("1 + True").parse_with_timing::<u64>().is_err());
+    }
+
+    #[test]
+    fn parse_schema() {
+        use serde_dhall::SimpleType;
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Server {
+            port: u16,
+            host: Option<String>,
+        }
+
+        let (ty, default) = from_str(
+            "{ Type = { port : Natural, host : Optional Text }, \
+             default = { port = 8080, host = None Text } }",
+        )
+        .parse_schema()
+        .unwrap();
+        assert_eq!(
+            ty,
+            from_str("{ port : Natural, host : Optional Text }")
+                .parse::<SimpleType>()
+                .unwrap()
+        );
+        assert_eq!(
+            default.deserialize::<Server>().unwrap(),
+            Server {
+                port: 8080,
+                host: None
+            }
+        );
+
+        let err = |s: &str| from_str(s).parse_schema().unwrap_err().to_string();
+        assert!(err("{ Type = { port : Natural } }").contains("`default`"));
+        assert!(err("{ default = { port = 1 } }").contains("`Type`"));
+        assert!(err("{ Type = 1, default = {=} }").contains("simple type"));
+    }
+
+    #[test]
+    fn field_case_insensitive() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Inner {
+            value: bool,
+        }
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Config {
+            port: u16,
+            host_name: String,
+            inner: Inner,
+        }
+        let parse = |s: &str| {
+            from_str(s)
+                .with_field_case_insensitive(true)
+                .parse::<Config>()
+                .map_err(|e| e.to_string())
+        };
+        let expected = Config {
+            port: 80,
+            host_name: "a".to_owned(),
+            inner: Inner { value: true },
+        };
+
+        let data = r#"{ Port = 80, HOST_NAME = "a", Inner.Value = True }"#;
+        assert_eq!(parse(data), Ok(expected));
+        assert!(from_str(data).parse::<Config>().is_err());
+        let err = parse(
+            r#"{ port = 80, Port = 80, host_name = "a", inner.value = True }"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("only differ in case"), "{}", err);
+
+        let lazy = from_str(data)
+            .with_field_case_insensitive(true)
+            .parse_lazy()
+            .unwrap();
+        assert_eq!(lazy.get::<Inner>("Inner").unwrap(), Inner { value: true });
+    }
+
+    #[test]
+    fn naturals_as_bools() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Flags {
+            a: bool,
+            b: bool,
+            c: bool,
+        }
+        let parse = |s: &str| {
+            from_str(s)
+                .naturals_as_bools(true)
+                .parse::<Flags>()
+                .map_err(|e| e.to_string())
+        };
+
+        assert_eq!(
+            parse("{ a = 0, b = 1, c = True }"),
+            Ok(Flags {
+                a: false,
+                b: true,
+                c: true
+            })
+        );
+        let err = parse("{ a = 0, b = 2, c = True }").unwrap_err();
+        assert!(err.contains("expected 0 or 1"), "{}", err);
+        assert!(from_str("{ a = 0, b = 1, c = True }")
+            .parse::<Flags>()
+            .is_err());
+        // Naturals are still deserialized as numbers elsewhere.
+        assert_eq!(
+            from_str("1")
+                .naturals_as_bools(true)
+                .parse::<u64>()
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn numbers_as_doubles() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Scale {
+            x: f64,
+            y: f32,
+        }
+        let parse = |s: &str, lenient: bool| {
+            from_str(s)
+                .numbers_as_doubles(lenient)
+                .parse::<Scale>()
+                .map_err(|e| e.to_string())
+        };
+
+        assert_eq!(
+            parse("{ x = 3.0, y = -1.5 }", false),
+            Ok(Scale { x: 3.0, y: -1.5 })
+        );
+        assert_eq!(
+            parse("{ x = 3, y = -1.5 }", false),
+            Err("expected a value of type Double, found a value of type Natural at `x`".to_owned())
+        );
+        assert_eq!(
+            parse("{ x = 3.0, y = -1 }", false),
+            Err("expected a value of type Double, found a value of type Integer at `y`".to_owned())
+        );
+        assert_eq!(
+            parse("{ x = 3, y = -1 }", true),
+            Ok(Scale { x: 3.0, y: -1.0 })
+        );
+    }
+
+    #[test]
+    fn allow_extra_fields() {
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        struct Server {
+            host: String,
+            port: Option<u16>,
+        }
+        let parse = |s: &str, allow: bool| {
+            from_str(s)
+                .static_type_annotation()
+                .allow_extra_fields(allow)
+                .parse::<Vec<Server>>()
+                .map_err(|e| e.to_string())
+        };
+        let server = |host: &str, port| Server {
+            host: host.to_owned(),
+            port,
+        };
+
+        let exact = r#"[{ host = "a", port = Some 80 }]"#;
+        assert_eq!(parse(exact, false).unwrap(), vec![server("a", Some(80))]);
+        assert_eq!(parse(exact, true).unwrap(), vec![server("a", Some(80))]);
+
+        let superset = r#"[{ host = "a", port = Some 80, tls = True }]"#;
+        assert!(parse(superset, false).is_err());
+        assert_eq!(parse(superset, true).unwrap(), vec![server("a", Some(80))]);
+
+        // Only extra fields are allowed, not missing ones, even if they are optional.
+        let no_port = r#"[{ host = "a", tls = True }]"#;
+        assert!(parse(no_port, true)
+            .unwrap_err()
+            .contains("but was annotated with"));
+
+        assert!(parse(r#"[{ port = Some 80, tls = True }]"#, true).is_err());
+        assert!(parse(r#"[{ host = 1, port = Some 80 }]"#, true).is_err());
+
+        // Unions must have the same alternatives, whose records may have extra fields.
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        enum Source {
+            Local { path: String },
+            Remote(String),
+        }
+        let parse_source = |s: &str| {
+            from_str(s)
+                .static_type_annotation()
+                .allow_extra_fields(true)
+                .parse::<Source>()
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            parse_source(
+                r#"< Local : { path : Text, mode : Natural } | Remote : Text >.Local { path = "a", mode = 1 }"#
+            )
+            .unwrap(),
+            Source::Local {
+                path: "a".to_owned()
+            }
+        );
+        assert!(parse_source(
+            r#"< Local : { path : Text } | Remote : Text | Other >.Remote "b""#
+        )
+        .is_err());
+        assert!(parse_source(
+            r#"< Local : { path : Text } >.Local { path = "a" }"#
+        )
+        .is_err());
+
+        // The extra fields are dropped before deserializing.
+        let ty: SimpleType = from_str("{ x : Natural }").parse().unwrap();
+        let map = from_str("{ x = 1, y = 2 }")
+            .type_annotation(&ty)
+            .allow_extra_fields(true)
+            .parse::<collections::BTreeMap<String, u64>>()
+            .unwrap();
+        assert_eq!(map.into_iter().collect::<Vec<_>>(), vec![("x".into(), 1)]);
+    }
+
+    #[test]
+    fn deny_unknown_fields() {
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        #[serde(deny_unknown_fields)]
+        struct Point {
+            x: u64,
+            y: u64,
+        }
+
+        assert_eq!(
+            from_str("{ x = 1, y = 2 }").parse::<Point>().unwrap(),
+            Point { x: 1, y: 2 }
+        );
+        let err = from_str("{ x = 1, y = 2, z = 3 }")
+            .parse::<Point>()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown field `z`"), "{}", err);
+        assert!(from_str("{ X = 1, y = 2, z = 3 }")
+            .with_field_case_insensitive(true)
+            .parse::<Point>()
+            .is_err());
+        // Fields dropped by `allow_extra_fields` are not reported.
+        assert_eq!(
+            from_str("{ x = 1, y = 2, z = 3 }")
+                .static_type_annotation()
+                .allow_extra_fields(true)
+                .parse::<Point>()
+                .unwrap(),
+            Point { x: 1, y: 2 }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn base64() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Blob {
+            #[serde(with = "serde_dhall::base64")]
+            data: Vec<u8>,
+        }
+        let parse =
+            |s: &str| from_str(s).parse::<Blob>().map_err(|e| e.to_string());
+
+        assert_eq!(parse(r#"{ data = "" }"#), Ok(Blob { data: Vec::new() }));
+        assert_eq!(
+            parse(r#"{ data = "aGVsbG8=" }"#),
+            Ok(Blob {
+                data: b"hello".to_vec()
+            })
+        );
+        let err = parse(r#"{ data = "not base64!" }"#).unwrap_err();
+        assert!(err.contains("invalid base64 `not base64!`"), "{}", err);
+        assert!(parse("{ data = [ 1, 2 ] }").is_err());
+
+        let blob = Blob {
+            data: vec![0, 1, 2, 255],
+        };
+        assert_eq!(
+            serialize(&blob).to_string().unwrap(),
+            r#"{ data = "AAEC/w==" }"#
+        );
+    }
+
+    #[test]
+    fn with_adapters() {
+        use date::Date;
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        mod timestamp {
+            use ::serde::{Deserialize, Deserializer, Serializer};
+            use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+            pub fn serialize<S: Serializer>(
+                t: &SystemTime,
+                s: S,
+            ) -> Result<S::Ok, S::Error> {
+                let secs = t.duration_since(UNIX_EPOCH).unwrap().as_secs();
+                s.serialize_u64(secs)
+            }
+            pub fn deserialize<'de, D: Deserializer<'de>>(
+                d: D,
+            ) -> Result<SystemTime, D::Error> {
+                let secs = u64::deserialize(d)?;
+                Ok(UNIX_EPOCH + Duration::from_secs(secs))
+            }
+        }
+
+        mod date {
+            use ::serde::de::Error;
+            use ::serde::{Deserialize, Deserializer, Serializer};
+
+            #[derive(Debug, PartialEq)]
+            pub struct Date(pub u16, pub u8, pub u8);
+
+            pub fn serialize<S: Serializer>(
+                d: &Date,
+                s: S,
+            ) -> Result<S::Ok, S::Error> {
+                s.serialize_str(&format!("{:04}-{:02}-{:02}", d.0, d.1, d.2))
+            }
+            pub fn deserialize<'de, D: Deserializer<'de>>(
+                d: D,
+            ) -> Result<Date, D::Error> {
+                let text = String::deserialize(d)?;
+                let parts: Vec<&str> = text.split('-').collect();
+                match parts.as_slice() {
+                    [y, m, d] => match (y.parse(), m.parse(), d.parse()) {
+                        (Ok(y), Ok(m), Ok(d)) => Ok(Date(y, m, d)),
+                        _ => Err(D::Error::custom("invalid date")),
+                    },
+                    _ => Err(D::Error::custom("invalid date")),
+                }
+            }
+        }
+
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Release {
+            #[serde(with = "timestamp")]
+            built_at: SystemTime,
+            #[serde(with = "date")]
+            released_on: Date,
+        }
+        let parse =
+            |s: &str| from_str(s).parse::<Release>().map_err(|e| e.to_string());
+
+        let release = Release {
+            built_at: UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+            released_on: Date(2020, 9, 13),
+        };
+        let text = r#"{ built_at = 1600000000, released_on = "2020-09-13" }"#;
+        assert_eq!(parse(text), Ok(release));
+        assert_eq!(
+            parse(
+                r#"{ built_at = Natural/subtract 1 1600000000, released_on = "2020-09-13" }"#
+            ),
+            Ok(Release {
+                built_at: UNIX_EPOCH + Duration::from_secs(1_599_999_999),
+                released_on: Date(2020, 9, 13),
+            })
+        );
+        let err =
+            parse(r#"{ built_at = 0, released_on = "soon" }"#).unwrap_err();
+        assert!(err.contains("invalid date"), "{}", err);
+        assert!(parse(r#"{ built_at = "now", released_on = "2020-09-13" }"#)
+            .is_err());
+        assert!(
+            parse(r#"{ built_at = -1, released_on = "2020-09-13" }"#).is_err()
+        );
+
+        let release = parse(text).unwrap();
+        assert_eq!(serialize(&release).to_string().unwrap(), text);
+    }
+
+    #[test]
+    fn coerce_optional() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Config {
+            port: Option<u16>,
+            host: Option<String>,
+            user: Option<String>,
+            tags: Option<Option<Vec<String>>>,
+        }
+        let data = r#"{ port = 80, host = Some "a", tags = ["x"] }"#;
+
+        assert_eq!(
+            from_str(data)
+                .coerce_optional(true)
+                .parse::<Config>()
+                .unwrap(),
+            Config {
+                port: Some(80),
+                host: Some("a".to_owned()),
+                user: None,
+                tags: Some(Some(vec!["x".to_owned()])),
+            }
+        );
+        assert!(from_str(data).parse::<Config>().is_err());
+        assert_eq!(
+            from_str("None Natural")
+                .coerce_optional(true)
+                .parse::<Option<u64>>()
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn adjacently_tagged_enum() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(tag = "tag", content = "contents")]
+        enum Shape {
+            Circle(u64),
+            Rectangle { width: u64, height: u64 },
+            Point,
+        }
+        let parse =
+            |s: &str| from_str(s).parse::<Shape>().map_err(|e| e.to_string());
+
+        assert_eq!(
+            parse(r#"{ tag = "Circle", contents = 2 }"#),
+            Ok(Shape::Circle(2))
+        );
+        assert_eq!(
+            parse(
+                r#"{ tag = "Rectangle", contents = { width = 1, height = 2 } }"#
+            ),
+            Ok(Shape::Rectangle {
+                width: 1,
+                height: 2
+            })
+        );
+        assert_eq!(parse(r#"{ tag = "Point" }"#), Ok(Shape::Point));
+        assert!(parse(r#"{ tag = "Triangle", contents = 1 }"#).is_err());
+        assert!(parse(r#"{ tag = "Circle" }"#).is_err());
+        assert!(parse("< Circle : Natural >.Circle 2").is_err());
+
+        // Variants with different payloads can be mixed in a record.
+        let shapes: collections::BTreeMap<String, Shape> = from_str(
+            r#"{ a = { tag = "Circle", contents = 1 }, b = { tag = "Point" } }"#,
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(shapes["a"], Shape::Circle(1));
+        assert_eq!(shapes["b"], Shape::Point);
+    }
+
+    #[test]
+    fn with_source() {
+        let template = from_str("True").naturals_as_bools(true).imports(false);
+        assert_eq!(template.parse::<bool>().ok(), Some(true));
+        assert_eq!(
+            template.with_source_str("0").parse::<bool>().ok(),
+            Some(false)
+        );
+        assert!(template
+            .with_source_str("./tests/prelude/Text/concatSep.dhall")
+            .parse::<bool>()
+            .is_err());
+    }
+
+    #[test]
+    fn validate() {
+        let ty: SimpleType =
+            from_str("{ name : Text, port : Natural }").parse().unwrap();
+        assert!(serde_dhall::validate("{ name = \"db\", port = 5432 }", &ty)
+            .is_ok());
+        assert!(serde_dhall::validate("{ name = \"db\" }", &ty).is_err());
+        assert!(serde_dhall::validate("{ name = 1 + True }", &ty).is_err());
+        assert!(serde_dhall::validate("{ name = ", &ty).is_err());
+    }
+
+    #[test]
+    fn is_subtype_of() {
+        fn ty(s: &str) -> SimpleType {
+            from_str(s).parse().unwrap()
+        }
+        let subtype = |a, b| ty(a).is_subtype_of(&ty(b));
+
+        assert!(subtype("Natural", "Natural"));
+        assert!(!subtype("Natural", "Integer"));
+        assert!(!subtype("Natural", "Optional Natural"));
+        assert!(subtype("{ x : Bool, y : Text }", "{ x : Bool }"));
+        assert!(!subtype("{ x : Bool }", "{ x : Bool, y : Text }"));
+        assert!(subtype("{ x : Bool }", "{ x : Bool, y : Optional Text }"));
+        assert!(!subtype("{ x : Natural }", "{ x : Bool }"));
+        assert!(subtype("List { x : Bool, y : Text }", "List { x : Bool }"));
+        assert!(subtype("< A | B : Natural >", "< A | B : Natural | C >"));
+        assert!(!subtype("< A | B : Natural | C >", "< A | B : Natural >"));
+        assert!(!subtype("< A : Natural >", "< A >"));
+        assert!(subtype(
+            "< A : { x : Bool, y : Text } >",
+            "< A : { x : Bool } >"
+        ));
+    }
+
+    #[test]
+    fn type_accessors() {
+        let ty = |s: &str| from_str(s).parse::<SimpleType>().unwrap();
+
+        let record = ty("{ b : List Bool, a : < X | Y : Text > }");
+        assert_eq!(
+            record.record_fields(),
+            Some(vec![
+                ("a".to_owned(), ty("< X | Y : Text >")),
+                ("b".to_owned(), ty("List Bool")),
+            ])
+        );
+        assert_eq!(record.union_variants(), None);
+        assert_eq!(ty("{}").record_fields(), Some(vec![]));
+
+        let union = ty("< Y : Text | X >");
+        assert_eq!(
+            union.union_variants(),
+            Some(vec![
+                ("X".to_owned(), None),
+                ("Y".to_owned(), Some(SimpleType::Text)),
+            ])
+        );
+        assert_eq!(union.record_fields(), None);
+        assert_eq!(ty("Optional { a : Bool }").record_fields(), None);
+    }
+
+    #[test]
+    fn untagged() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(untagged)]
+        enum E {
+            N(u64),
+            S(String),
+        }
+        let parse =
+            |s: &str| from_str(s).parse::<E>().map_err(|e| e.to_string());
+
+        assert_eq!(parse("40 + 2"), Ok(E::N(42)));
+        assert_eq!(parse(r#""forty-two""#), Ok(E::S("forty-two".to_owned())));
+        assert_eq!(
+            from_str("[ 1, 2 ]").parse::<Vec<E>>().unwrap(),
+            vec![E::N(1), E::N(2)]
+        );
+        let err = parse("True").unwrap_err();
+        assert!(
+            err.contains("did not match any variant of untagged enum E"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn self_describing() {
+        use serde_json::json;
+
+        let config = r#"
+            { name = "app"
+            , replicas = 3
+            , offset = -2
+            , ratio = 0.5
+            , debug = False
+            , owner = None Text
+            , region = Some "eu"
+            , hosts = [ { host = "a", port = 80 }, { host = "b", port = 81 } ]
+            , env = toMap { LANG = "C" }
+            }
+        "#;
+        let value = from_str(config).parse::<serde_json::Value>().unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "name": "app",
+                "replicas": 3,
+                "offset": -2,
+                "ratio": 0.5,
+                "debug": false,
+                "owner": null,
+                "region": "eu",
+                "hosts": [
+                    { "host": "a", "port": 80 },
+                    { "host": "b", "port": 81 }
+                ],
+                "env": { "LANG": "C" }
+            })
+        );
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(untagged)]
+        enum Port {
+            Number(u64),
+            Name(String),
+            Range { from: u64, to: u64 },
+        }
+        // Dhall lists are homogeneous, so the different shapes are in a record.
+        let ports = from_str(
+            r#"{ http = 80, admin = "admin", dynamic = { from = 8000, to = 8100 } }"#,
+        )
+        .parse::<collections::BTreeMap<String, Port>>()
+        .unwrap();
+        assert_eq!(
+            ports.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("admin".to_owned(), Port::Name("admin".to_owned())),
+                (
+                    "dynamic".to_owned(),
+                    Port::Range {
+                        from: 8000,
+                        to: 8100
+                    }
+                ),
+                ("http".to_owned(), Port::Number(80)),
+            ]
+        );
+    }
+
+    #[test]
+    fn renamed_variants() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        enum Level {
+            #[serde(rename = "1")]
+            One,
+            #[serde(rename = "2")]
+            Two,
+            #[serde(rename = "3")]
+            Custom(u64),
+        }
+        let ty = from_str("< `1` | `2` | `3` : Natural >")
+            .parse::<SimpleType>()
+            .unwrap();
+        let parse = |s: &str| {
+            from_str(s)
+                .type_annotation(&ty)
+                .parse::<Level>()
+                .map_err(|e| e.to_string())
+        };
+
+        assert_eq!(parse("< `1` | `2` | `3` : Natural >.`1`"), Ok(Level::One));
+        assert_eq!(parse("< `1` | `2` | `3` : Natural >.`2`"), Ok(Level::Two));
+        assert_eq!(
+            parse("< `1` | `2` | `3` : Natural >.`3` 7"),
+            Ok(Level::Custom(7))
+        );
+        let err = from_str("< One | `2` >.One")
+            .parse::<Level>()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown variant `One`"), "{}", err);
+
+        assert_eq!(
+            serialize(&Level::Two)
+                .type_annotation(&ty)
+                .to_string()
+                .unwrap(),
+            "< `1` | `2` | `3`: Natural >.`2`"
+        );
+    }
+
+    #[test]
+    fn aliases() {
+        #[derive(Debug, Clone, PartialEq, Deserialize)]
+        struct Config {
+            #[serde(alias = "host_name", alias = "server")]
+            host: String,
+            port: u64,
+        }
+        let expected = Config {
+            host: "example.com".to_owned(),
+            port: 80,
+        };
+
+        for src in &[
+            r#"{ host = "example.com", port = 80 }"#,
+            r#"{ host_name = "example.com", port = 80 }"#,
+            r#"{ server = "example.com", port = 80 }"#,
+        ] {
+            assert_eq!(from_str(src).parse::<Config>().unwrap(), expected);
+        }
+        assert!(from_str(r#"{ hostname = "example.com", port = 80 }"#)
+            .parse::<Config>()
+            .is_err());
+    }
+
+    #[test]
+    fn error_path() {
+        #[derive(Debug, Deserialize)]
+        struct Listener {
+            #[allow(dead_code)]
+            port: u16,
+        }
+        #[derive(Debug, Deserialize)]
+        enum Mode {
+            #[allow(dead_code)]
+            Fixed(Listener),
+        }
+        #[derive(Debug, Deserialize)]
+        struct Server {
+            #[allow(dead_code)]
+            listeners: Vec<Listener>,
+            #[allow(dead_code)]
+            mode: Option<Mode>,
+        }
+        #[derive(Debug, Deserialize)]
+        struct Config {
+            #[allow(dead_code)]
+            server: Server,
+            #[allow(dead_code)]
+            limits: collections::HashMap<String, u8>,
+        }
+        let err = |s: &str| {
+            let err = from_str(s).parse::<Config>().unwrap_err();
+            (err.path().map(str::to_owned), err.to_string())
+        };
+        let config = |listeners: &str, mode: &str, limits: &str| {
+            format!(
+                "{{ server = {{ listeners = {}, mode = {} }}, limits = {} }}",
+                listeners, mode, limits
+            )
+        };
+        let fixed = "let Mode = < Fixed : { port : Natural } > in Some (Mode.Fixed { port = 1 })";
+        let limits = "toMap { cpu = 2 }";
+
+        assert_eq!(
+            err(&config(
+                "[ { port = 80 }, { port = 443 }, { port = 70000 } ]",
+                fixed,
+                limits
+            )),
+            (
+                Some("server.listeners[2].port".to_owned()),
+                "integer 70000 is out of range for u16 at `server.listeners[2].port`"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            err(&config(
+                "[ { port = 80 } ]",
+                "Some (< Fixed : { porrt : Natural } >.Fixed { porrt = 1 })",
+                limits
+            ))
+            .1,
+            "missing field `port` at `server.mode.Fixed`"
+        );
+        assert_eq!(
+            err(&config(
+                "[] : List { port : Natural }",
+                "let Mode = < Fixed : { port : Natural } > in Some (Mode.Fixed { port = 100000 })",
+                limits
+            ))
+            .0,
+            Some("server.mode.Fixed.port".to_owned())
+        );
+        assert_eq!(
+            err(&config(
+                "[] : List { port : Natural }",
+                fixed,
+                "toMap { cpu = 2, mem = 512 }"
+            ))
+            .0,
+            Some("limits.mem".to_owned())
+        );
+        // Errors at the top level have no path.
+        let err = from_str("{ limits = {=} }").parse::<Config>().unwrap_err();
+        assert_eq!(err.path(), None);
+    }
+
+    #[test]
+    fn codegen() {
+        let schema = "{ name : Text, \
+                      server : { host : Text, port : Optional Natural }, \
+                      log-level : < Debug | Info | Custom : { level : Natural } >, \
+                      tags : List { key : Text, value : Text }, \
+                      type : Bool, \
+                      ratio : Double, \
+                      offsetX : Integer, \
+                      fooBar : Bool, \
+                      foo_bar : Bool, \
+                      mode : < A | a > }";
+        let ty = from_str(schema).parse::<SimpleType>().unwrap();
+        let code = serde_dhall::codegen::rust_types(&ty, "config");
+        // The expected output is compiled below, so the generated code is known to compile and
+        // values of the schema can be read into it.
+        assert_eq!(code, include_str!("codegen/config.rs"));
+        mod generated {
+            include!("codegen/config.rs");
+        }
+        use generated::*;
+        let config = from_str(
+            r#"
+            let LogLevel = < Debug | Info | Custom : { level : Natural } >
+            in  { name = "app"
+                , server = { host = "localhost", port = Some 80 }
+                , log-level = LogLevel.Custom { level = 3 }
+                , tags = [ { key = "env", value = "prod" } ]
+                , type = True
+                , ratio = 0.5
+                , offsetX = -2
+                , fooBar = True
+                , foo_bar = False
+                , mode = < A | a >.a
+                }
+            "#,
+        )
+        .type_annotation(&ty)
+        .parse::<Config>()
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                foo_bar: true,
+                foo_bar2: false,
+                log_level: ConfigLogLevel::Custom(ConfigLogLevelCustom {
+                    level: 3
+                }),
+                mode: ConfigMode::A2,
+                name: "app".to_owned(),
+                offset_x: -2,
+                ratio: 0.5,
+                server: ConfigServer {
+                    host: "localhost".to_owned(),
+                    port: Some(80),
+                },
+                tags: vec![ConfigTags {
+                    key: "env".to_owned(),
+                    value: "prod".to_owned(),
+                }],
+                r#type: true,
+            }
+        );
+
+        let items = from_str("List < A | B : { x : Bool } >")
+            .parse::<SimpleType>()
+            .unwrap();
+        assert_eq!(
+            serde_dhall::codegen::rust_types(&items, "Items"),
+            r#"pub type Items = Vec<ItemsItem>;
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub enum ItemsItem {
+    A,
+    B(ItemsItemB),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct ItemsItemB {
+    pub x: bool,
+}
+"#
+        );
+    }
+
+    #[test]
+    fn parse_all_fields_as() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        enum Shape {
+            Circle(u64),
+            Square(u64),
+            Point,
+        }
+
+        let data = "{ a = < Circle : Natural >.Circle 1, \
+                    b = < Square : Natural | Point >.Square 2, \
+                    c = < Point | Circle : Natural >.Point }";
+        let map = from_str(data).parse_all_fields_as::<Shape>().unwrap();
+        let mut expected = collections::HashMap::new();
+        expected.insert("a".to_owned(), Shape::Circle(1));
+        expected.insert("b".to_owned(), Shape::Square(2));
+        expected.insert("c".to_owned(), Shape::Point);
+        assert_eq!(map, expected);
+
+        // Without a type annotation a plain `HashMap` works the same way.
+        assert_eq!(
+            from_str(data)
+                .parse::<collections::HashMap<String, Shape>>()
+                .unwrap(),
+            expected
+        );
+
+        let err = |s: &str| {
+            from_str(s)
+                .parse_all_fields_as::<Shape>()
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(err("[1, 2]").contains("expected a record"));
+        assert!(err("{ a = < Triangle >.Triangle }").contains("Triangle"));
+    }
+
+    #[test]
+    fn parse_or_default() {
+        #[derive(Debug, PartialEq, Default, Deserialize, StaticType)]
+        struct Config {
+            port: u16,
+            verbose: bool,
+        }
+
+        let (data, err) = from_str("{ port = 8080, verbose = True }")
+            .static_type_annotation()
+            .parse_or_default::<Config>();
+        assert_eq!(
+            data,
+            Config {
+                port: 8080,
+                verbose: true
+            }
+        );
+        assert!(err.is_none());
+
+        for src in &["{ port = 8080 }", "{ port = ", "./missing.dhall"] {
+            let (data, err) = from_str(src)
+                .static_type_annotation()
+                .parse_or_default::<Config>();
+            assert_eq!(data, Config::default());
+            assert!(err.is_some());
+        }
+    }
+
+    #[test]
+    fn parse_with_defaults() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize, StaticType)]
+        struct Config {
+            port: u16,
+            hosts: Vec<String>,
+            user: Option<String>,
+        }
+        let defaults = || Config {
+            port: 80,
+            hosts: vec![],
+            user: None,
+        };
+
+        assert_eq!(
+            from_str("{ port = 8080 }")
+                .static_type_annotation()
+                .parse_with_defaults(defaults())
+                .unwrap(),
+            Config {
+                port: 8080,
+                hosts: vec![],
+                user: None,
+            }
+        );
+        assert_eq!(
+            from_str("{ hosts = [\"a\"], user = Some \"me\" }")
+                .static_type_annotation()
+                .parse_with_defaults(defaults())
+                .unwrap(),
+            Config {
+                port: 80,
+                hosts: vec!["a".to_owned()],
+                user: Some("me".to_owned()),
+            }
+        );
+        // The merged record must still match the annotation.
+        assert!(from_str("{ port = True }")
+            .static_type_annotation()
+            .parse_with_defaults(defaults())
+            .is_err());
+    }
+
+    #[test]
+    fn from_files() {
+        use std::fs;
+
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        struct Config {
+            host: String,
+            port: u16,
+            debug: bool,
+        }
+
+        let dir = fixture_dir(
+            "from_files",
+            &[
+                (
+                    "base.dhall",
+                    r#"{ host = "example.com", port = 80, debug = False }"#,
+                ),
+                // Imports are relative to the file they appear in.
+                ("local/port.dhall", "8080"),
+                (
+                    "local/override.dhall",
+                    "{ port = ./port.dhall, debug = True }",
+                ),
+                ("bad.dhall", "{ port = True }"),
+            ],
+        );
+
+        let parse = |files: &[&str]| {
+            serde_dhall::from_files(files.iter().map(|f| dir.join(f)))
+                .static_type_annotation()
+                .parse::<Config>()
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            parse(&["base.dhall", "local/override.dhall"]),
+            Ok(Config {
+                host: "example.com".to_owned(),
+                port: 8080,
+                debug: true,
+            })
+        );
+        assert_eq!(
+            parse(&["local/override.dhall", "base.dhall"]),
+            Ok(Config {
+                host: "example.com".to_owned(),
+                port: 80,
+                debug: false,
+            })
+        );
+        // The merged record is checked against the annotation.
+        assert!(parse(&["local/override.dhall"]).is_err());
+        assert!(parse(&["base.dhall", "bad.dhall"]).is_err());
+        assert!(parse(&[]).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn env_vars() {
+        use std::fs;
+
+        let dir = fixture_dir(
+            "env_vars",
+            &[
+                (
+                    "good.env",
+                    "# Build settings\n\nexport NAME=\"my app\"\nPORT = 8080\nEMPTY=\n",
+                ),
+                ("bad.env", "PORT=1\nnot a variable\n"),
+            ],
+        );
+
+        let parse = |src: &str, file: &str| {
+            from_str(src)
+                .env_file(dir.join(file))
+                .env_vars(vec![("EMPTY".to_string(), "set".to_string())])
+                .parse::<String>()
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(parse("env:NAME as Text", "good.env"), Ok("my app".into()));
+        assert_eq!(
+            parse("Natural/show env:PORT", "good.env"),
+            Ok("8080".into())
+        );
+        // Explicit values win over the file.
+        assert_eq!(parse("env:EMPTY as Text", "good.env"), Ok("set".into()));
+        assert_eq!(
+            parse("env:NAME as Text", "bad.env"),
+            Err(format!(
+                "{}:2: expected `KEY=VALUE`, found `not a variable`",
+                dir.join("bad.env").display()
+            ))
+        );
+        assert!(parse("env:NAME as Text", "missing.env")
+            .unwrap_err()
+            .starts_with("cannot read env file"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn canonicalize_paths() {
+        use std::fs;
+        use std::os::unix::fs::symlink;
+
+        let dir = fixture_dir(
+            "canonicalize_paths",
+            &[
+                ("real/a.dhall", "./b.dhall"),
+                ("real/b.dhall", "1"),
+                ("link/b.dhall", "2"),
+                ("main.dhall", "./link/a.dhall"),
+            ],
+        );
+        symlink(dir.join("real/a.dhall"), dir.join("link/a.dhall")).unwrap();
+
+        let parse = |path: &str, canonicalize: bool| {
+            serde_dhall::from_file(dir.join(path))
+                .canonicalize_paths(canonicalize)
+                .parse::<u64>()
+                .unwrap()
+        };
+        // Relative imports are resolved from the symlink's directory by default, and from the
+        // actual file's directory with canonicalization.
+        assert_eq!(parse("main.dhall", false), 2);
+        assert_eq!(parse("main.dhall", true), 1);
+        assert_eq!(parse("link/a.dhall", false), 2);
+        assert_eq!(parse("link/a.dhall", true), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn root_directory() {
+        use std::fs;
+        use std::os::unix::fs::symlink;
+
+        let dir = fixture_dir(
+            "root_directory",
+            &[
+                ("secret.dhall", "42"),
+                ("root/sub/one.dhall", "1"),
+                ("root/ok.dhall", "./sub/one.dhall"),
+                ("root/sub/up.dhall", "../ok.dhall"),
+                ("root/parent.dhall", "../secret.dhall"),
+                ("root/via_link.dhall", "./link.dhall"),
+            ],
+        );
+        // This one needs the directory's absolute path.
+        fs::write(
+            dir.join("root/absolute.dhall"),
+            dir.join("secret.dhall").to_str().unwrap(),
+        )
+        .unwrap();
+        symlink(dir.join("secret.dhall"), dir.join("root/link.dhall")).unwrap();
+
+        let parse = |file: &str| {
+            serde_dhall::from_file(dir.join("root").join(file))
+                .root_directory(dir.join("root"))
+                .parse::<u64>()
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(parse("ok.dhall"), Ok(1));
+        assert_eq!(parse("sub/up.dhall"), Ok(1));
+        for file in &["parent.dhall", "absolute.dhall", "via_link.dhall"] {
+            let err = parse(file).unwrap_err();
+            assert!(err.contains("ImportOutsideRoot"), "{}: {}", file, err);
+        }
+        // Without a root, the same imports are allowed.
+        assert_eq!(
+            serde_dhall::from_file(dir.join("root/via_link.dhall"))
+                .parse::<u64>()
+                .unwrap(),
+            42
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn env_imports() {
+        std::env::set_var("SERDE_DHALL_TEST_CODE", "{ x = 1 + 1 }");
+        std::env::set_var("SERDE_DHALL_TEST_TEXT", r#"{"x": 2}"#);
+        std::env::set_var("SERDE_DHALL_TEST_BAD", "{ x = }");
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Foo {
+            x: u64,
+        }
+        assert_eq!(
+            from_str("env:SERDE_DHALL_TEST_CODE")
+                .parse::<Foo>()
+                .unwrap(),
+            Foo { x: 2 }
+        );
+        assert_eq!(
+            from_str("env:SERDE_DHALL_TEST_TEXT as Text")
+                .parse::<String>()
+                .unwrap(),
+            r#"{"x": 2}"#
+        );
+        let err = from_str("env:SERDE_DHALL_TEST_BAD")
+            .parse::<Foo>()
+            .unwrap_err();
+        assert!(err.to_string().contains("env:SERDE_DHALL_TEST_BAD"));
+    }
+
+    #[test]
+    fn as_location() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        enum Location {
+            Local(String),
+            Remote(String),
+            Environment(String),
+            Missing,
+        }
+        let parse = |s: &str| {
+            from_str(s).parse::<Location>().map_err(|e| e.to_string())
+        };
+
+        // The variable doesn't need to be set since the import isn't resolved.
+        assert_eq!(
+            parse("env:SERDE_DHALL_TEST_UNSET as Location"),
+            Ok(Location::Environment("SERDE_DHALL_TEST_UNSET".to_owned()))
+        );
+        assert_eq!(
+            parse("https://example.com/foo.dhall as Location"),
+            Ok(Location::Remote("https://example.com/foo.dhall".to_owned()))
+        );
+        assert_eq!(parse("missing as Location"), Ok(Location::Missing));
+        assert!(matches!(
+            parse("./foo.dhall as Location"),
+            Ok(Location::Local(path)) if path.ends_with("/foo.dhall")
+        ));
+        // This allows choosing between imports without resolving them.
+        assert_eq!(
+            from_str(
+                "merge { Local = \\(_ : Text) -> 1, Remote = \\(_ : Text) -> 2, \
+                 Environment = \\(_ : Text) -> 3, Missing = 4 } \
+                 (env:SERDE_DHALL_TEST_UNSET as Location)"
+            )
+            .parse::<u64>()
+            .unwrap(),
+            3
+        );
+    }
+
+    #[test]
+    fn on_import() {
+        use serde_dhall::imports::{ImportLocationKind, ImportMode};
+
+        std::env::set_var("SERDE_DHALL_TEST_ON_IMPORT", "\"b\"");
+        let mut log = Vec::new();
+        let data = from_str(
+            "./tests/prelude/Text/concatSep.dhall \"-\" \
+             [ \"a\", env:SERDE_DHALL_TEST_ON_IMPORT, env:SERDE_DHALL_TEST_ON_IMPORT as Text ]",
+        )
+        .on_import(|location, mode| {
+            let name = match location.kind() {
+                ImportLocationKind::Local(path) => {
+                    path.file_name().unwrap().to_string_lossy().into_owned()
+                }
+                ImportLocationKind::Env(name) => name.clone(),
+                _ => unreachable!(),
+            };
+            log.push((name, mode));
+        })
+        .parse::<String>()
+        .unwrap();
+
+        assert_eq!(data, "a-b-\"b\"");
+        assert_eq!(
+            log,
+            vec![
+                ("concatSep.dhall".to_owned(), ImportMode::Code),
+                ("SERDE_DHALL_TEST_ON_IMPORT".to_owned(), ImportMode::Code),
+                ("SERDE_DHALL_TEST_ON_IMPORT".to_owned(), ImportMode::RawText),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "reqwest")]
+    fn remote_import_headers() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        // Serves one request with the value `7`, and returns the request it received.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nConnection: close\r\n\r\n7")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let mut headers = Vec::new();
+        let data = from_str(&format!(
+            "http://127.0.0.1:{}/config.dhall using \
+             (toMap {{ Authorization = \"Bearer ${{env:TOKEN as Text}}\" }})",
+            port
+        ))
+        .env_vars(vec![("TOKEN".to_owned(), "secret".to_owned())])
+        .on_import(|location, _| headers.extend_from_slice(location.headers()))
+        .parse::<u64>()
+        .unwrap();
+
+        assert_eq!(data, 7);
+        assert_eq!(
+            headers,
+            vec![("Authorization".to_owned(), "Bearer secret".to_owned())]
+        );
+        let request = server.join().unwrap().to_lowercase();
+        assert!(request.contains("\r\nauthorization: bearer secret\r\n"));
+    }
+
+    #[test]
+    fn remote_import_bad_headers() {
+        // The headers are checked before anything is fetched. They must have the right type, and
+        // can't refer to variables in scope.
+        assert!(from_str("http://example.com/config.dhall using 3")
+            .parse::<u64>()
+            .unwrap_err()
+            .to_string()
+            .contains(
+                "annotated with `List { mapKey : Text, mapValue : Text }`"
+            ));
+        assert!(from_str(
+            "let h = [ { mapKey = \"a\", mapValue = \"b\" } ] \
+             in http://example.com/config.dhall using h"
+        )
+        .parse::<u64>()
+        .unwrap_err()
+        .to_string()
+        .contains("unbound variable `h`"));
+    }
+
+    #[test]
+    fn no_panic_on_bad_input() {
+        let inputs = [
+            "18446744073709551615 + 1",
+            "18446744073709551615 * 2",
+            "Integer/negate -9223372036854775808",
+            "[ { mapKey = 1, mapValue = 2 } ]",
+            "[] : List (Bool -> Bool)",
+            "https://example.invalid/foo.dhall",
+            "\"\\u{10FFFF}\"",
+        ];
+        for input in inputs.iter() {
+            let _ = from_str(input).parse::<serde_dhall::Value>();
+        }
+        assert!(from_str("[] : List (Bool -> Bool)")
+            .parse::<serde_dhall::Value>()
+            .is_err());
+    }
+
     // TODO: test various builder configurations
     // In particular test cloning and reusing builder
 }
@@ -41,6 +41,12 @@ fn test_static_type() {
         <(bool, Option<String>)>::static_type()
     );
 
+    #[derive(StaticType)]
+    #[allow(dead_code)]
+    struct Port(u16);
+    assert_eq!(Port::static_type(), parse("Natural"));
+    assert_eq!(<Vec<Port>>::static_type(), parse("List Natural"));
+
     #[derive(StaticType)]
     #[allow(dead_code)]
     struct D();
@@ -77,3 +83,216 @@ fn test_static_type() {
         parse("< A | B: Bool | C: { a: Bool, b: Natural } >")
     )
 }
+
+#[test]
+fn test_simple_type_serde() {
+    let ty: SimpleType = from_str(
+        "{ x: Optional Natural, y: List < A | B: Text >, z: { _1: Double } }",
+    )
+    .parse()
+    .unwrap();
+
+    let bytes = bincode::serialize(&ty).unwrap();
+    let reloaded: SimpleType = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(reloaded, ty);
+
+    let json = serde_json::to_string(&ty).unwrap();
+    let reloaded: SimpleType = serde_json::from_str(&json).unwrap();
+    assert_eq!(reloaded, ty);
+
+    // Types that aren't simple are still rejected.
+    assert!(from_str("Natural -> Natural")
+        .parse::<SimpleType>()
+        .is_err());
+    assert!(from_str("1").parse::<SimpleType>().is_err());
+    // Values aren't read as types, even when they look like the serialized form of one.
+    assert!(from_str("< Bool | Natural >.Bool")
+        .parse::<SimpleType>()
+        .is_err());
+    assert!(from_str("{ t = < Bool | Natural >.Bool }")
+        .parse::<std::collections::HashMap<String, SimpleType>>()
+        .is_err());
+    // Types are only read into `SimpleType`.
+    assert!(from_str("Natural").parse::<String>().is_err());
+    assert!(from_str("{ a = Natural }")
+        .parse::<std::collections::HashMap<String, String>>()
+        .is_err());
+    assert!(from_str("{ a = Natural }")
+        .parse::<serde_dhall::SimpleValue>()
+        .is_err());
+}
+
+#[test]
+fn test_simple_type_from_builtins() {
+    use std::collections::HashMap;
+    use SimpleType::*;
+
+    let ty = |s: &str| from_str(s).parse::<SimpleType>().unwrap();
+
+    assert_eq!(ty("Bool"), Bool);
+    assert_eq!(ty("Natural"), Natural);
+    assert_eq!(ty("Integer"), Integer);
+    assert_eq!(ty("Double"), Double);
+    assert_eq!(ty("Text"), Text);
+    assert_eq!(ty("List Text"), List(Box::new(Text)));
+    assert_eq!(ty("Optional Natural"), Optional(Box::new(Natural)));
+    assert_eq!(
+        ty("List (Optional (List Bool))"),
+        List(Box::new(Optional(Box::new(List(Box::new(Bool))))))
+    );
+    assert_eq!(
+        ty("{ a : Text, b : Optional Integer }"),
+        Record(
+            vec![
+                ("a".to_owned(), Text),
+                ("b".to_owned(), Optional(Box::new(Integer))),
+            ]
+            .into_iter()
+            .collect::<HashMap<_, _>>()
+        )
+    );
+    assert_eq!(
+        ty("< A | B : Double >"),
+        Union(
+            vec![("A".to_owned(), None), ("B".to_owned(), Some(Double))]
+                .into_iter()
+                .collect::<HashMap<_, _>>()
+        )
+    );
+    // Types are normalized first.
+    assert_eq!(ty("let T = Text in List T"), List(Box::new(Text)));
+    assert_eq!(
+        ty("{ a : Text } ⩓ { b : Bool }"),
+        ty("{ a : Text, b : Bool }")
+    );
+
+    // Builtins that aren't types aren't simple types.
+    assert!(from_str("List").parse::<SimpleType>().is_err());
+    assert!(from_str("Natural/even").parse::<SimpleType>().is_err());
+    assert!(from_str("Type").parse::<SimpleType>().is_err());
+}
+
+#[test]
+fn test_kind_level_record() {
+    use std::collections::HashMap;
+
+    let data = "{ Port = Natural, Host = Text, Nested = { Flag = Bool } }";
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Schema {
+        port: SimpleType,
+        host: SimpleType,
+        nested: HashMap<String, SimpleType>,
+    }
+    let schema: Schema = from_str(data).parse().unwrap();
+    assert_eq!(schema.port, SimpleType::Natural);
+    assert_eq!(schema.host, SimpleType::Text);
+    assert_eq!(schema.nested.get("Flag"), Some(&SimpleType::Bool));
+
+    let lazy = from_str(data).parse_lazy().unwrap();
+    assert_eq!(
+        lazy.get::<SimpleType>("Nested.Flag").unwrap(),
+        SimpleType::Bool
+    );
+
+    let value: serde_dhall::Value = from_str(data).parse().unwrap();
+    assert_eq!(
+        value.to_string(),
+        "{ Host = Text, Nested = { Flag = Bool }, Port = Natural }"
+    );
+}
+
+#[test]
+fn test_static_type_flatten() {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, StaticType)]
+    struct Metadata {
+        name: String,
+        version: u64,
+    }
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, StaticType)]
+    struct Package {
+        #[serde(flatten)]
+        metadata: Metadata,
+        dependencies: Vec<String>,
+    }
+
+    assert_eq!(
+        Package::static_type(),
+        from_str(
+            "{ name : Text, version : Natural, dependencies : List Text }"
+        )
+        .parse::<SimpleType>()
+        .unwrap()
+    );
+
+    let package = Package {
+        metadata: Metadata {
+            name: "dhall".to_owned(),
+            version: 1,
+        },
+        dependencies: vec!["serde".to_owned()],
+    };
+    let data = r#"{ dependencies = ["serde"], name = "dhall", version = 1 }"#;
+    assert_eq!(
+        from_str(data)
+            .static_type_annotation()
+            .parse::<Package>()
+            .unwrap(),
+        package
+    );
+    assert_eq!(
+        serde_dhall::serialize(&package)
+            .static_type_annotation()
+            .to_string()
+            .unwrap(),
+        data
+    );
+}
+
+#[test]
+fn test_simple_type_let_aliases() {
+    fn parse(s: &str) -> SimpleType {
+        from_str(s).parse().unwrap()
+    }
+
+    assert_eq!(parse("let T = Natural in T"), SimpleType::Natural);
+    assert_eq!(
+        parse("let Port = Natural let Host = Text in { p : Port, h : Host }"),
+        parse("{ p : Natural, h : Text }")
+    );
+    assert_eq!(
+        parse("let T = { x : Natural } in List (Optional T)"),
+        parse("List (Optional { x : Natural })")
+    );
+
+    // An alias can also be used in the type annotation of the data itself.
+    let ty = parse("{ p : Natural }");
+    assert!(from_str("let Port = Natural in { p = 1 } : { p : Port }")
+        .type_annotation(&ty)
+        .parse::<serde_dhall::Value>()
+        .is_ok());
+}
+
+#[test]
+#[should_panic(
+    expected = "field `name` of flattened `Metadata` is already defined"
+)]
+fn test_static_type_flatten_collision() {
+    use serde::Serialize;
+
+    #[derive(Serialize, StaticType)]
+    struct Metadata {
+        name: String,
+    }
+    #[derive(Serialize, StaticType)]
+    struct Package {
+        name: String,
+        #[serde(flatten)]
+        metadata: Metadata,
+    }
+
+    Package::static_type();
+}